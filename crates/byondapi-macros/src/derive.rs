@@ -0,0 +1,265 @@
+//! `#[derive(FromByond, ToByond)]`, mapping structs to datum vars or assoc lists
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+
+/// Where the struct's fields live in byondland
+enum Source {
+    /// Vars of a datum, with the type path to create on [`ToByond`] if there's one
+    Datum(Option<syn::LitStr>),
+    /// Keys of an assoc list
+    AssocList,
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: syn::Type,
+    /// Var or key name
+    name: syn::LitStr,
+    default: bool,
+    skip: bool,
+}
+
+struct Input {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    source: Source,
+    fields: Vec<Field>,
+}
+
+fn parse_input(input: syn::DeriveInput) -> syn::Result<Input> {
+    let mut path = None;
+    let mut assoc_list = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("byond"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("path") {
+                path = Some(meta.value()?.parse::<syn::LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("assoc_list") {
+                assoc_list = true;
+                Ok(())
+            } else {
+                Err(meta.error("Expected `path = \"..\"` or `assoc_list`"))
+            }
+        })?;
+    }
+    let source = match (path, assoc_list) {
+        (Some(path), true) => {
+            return Err(syn::Error::new(
+                path.span(),
+                "`path` can't be used together with `assoc_list`",
+            ))
+        }
+        (path, false) => Source::Datum(path),
+        (None, true) => Source::AssocList,
+    };
+
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(named),
+        ..
+    }) = input.data
+    else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "Only structs with named fields can be derived",
+        ));
+    };
+
+    let mut fields = Vec::new();
+    for field in named.named {
+        let ident = field.ident.expect("named fields have idents");
+        let mut name = syn::LitStr::new(&ident.unraw().to_string(), ident.span());
+        let mut default = false;
+        let mut skip = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("byond"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    default = true;
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected `rename = \"..\"`, `default` or `skip`"))
+                }
+            })?;
+        }
+        fields.push(Field {
+            ident,
+            ty: field.ty,
+            name,
+            default,
+            skip,
+        });
+    }
+
+    Ok(Input {
+        ident: input.ident,
+        generics: input.generics,
+        source,
+        fields,
+    })
+}
+
+/// Expression evaluating to the string id of `name`, looked up once and cached
+fn cached_str_id(name: &syn::LitStr) -> TokenStream {
+    quote! {{
+        static STRING_ID: ::std::sync::OnceLock<::byondapi::sys::u4c> = ::std::sync::OnceLock::new();
+        ::byondapi::byond_string::cached_str_id(&STRING_ID, #name)
+    }}
+}
+
+pub fn from_byond(input: syn::DeriveInput) -> syn::Result<TokenStream> {
+    let input = parse_input(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let check = match &input.source {
+        Source::Datum(_) => quote! {},
+        Source::AssocList => quote! {
+            if !value.is_list() {
+                return Err(::byondapi::Error::NotAList(*value));
+            }
+        },
+    };
+
+    let fields = input.fields.iter().map(|field| {
+        let Field {
+            ident, ty, name, ..
+        } = field;
+        if field.skip {
+            return quote! { #ident: ::std::default::Default::default() };
+        }
+        let read = match &input.source {
+            Source::Datum(_) => {
+                let str_id = cached_str_id(name);
+                quote! { #str_id.and_then(|id| value.read_var_id(id)) }
+            }
+            Source::AssocList => quote! {
+                ::byondapi::value::ByondValue::new_str(#name)
+                    .and_then(|key| value.read_list_index_internal(&key))
+            },
+        };
+        if field.default {
+            quote! {
+                #ident: match #read {
+                    Ok(var) if !var.is_null() => {
+                        <#ty as ::byondapi::value::conversion::FromByond>::from_byond(&var)?
+                    }
+                    _ => ::std::default::Default::default(),
+                }
+            }
+        } else {
+            quote! {
+                #ident: <#ty as ::byondapi::value::conversion::FromByond>::from_byond(&#read?)?
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::byondapi::value::conversion::FromByond for #ident #ty_generics #where_clause {
+            fn from_byond(
+                value: &::byondapi::value::ByondValue,
+            ) -> ::std::result::Result<Self, ::byondapi::Error> {
+                #check
+                Ok(Self {
+                    #(#fields,)*
+                })
+            }
+        }
+    })
+}
+
+pub fn to_byond(input: syn::DeriveInput) -> syn::Result<TokenStream> {
+    let input = parse_input(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let writes = input
+        .fields
+        .iter()
+        .filter(|field| !field.skip)
+        .map(|field| {
+            let Field { ident, name, .. } = field;
+            let value = format_ident!("__{}", ident.unraw());
+            let convert = quote! {
+                let #value = ::byondapi::value::conversion::ToByond::to_byond(&self.#ident)?;
+            };
+            match &input.source {
+                Source::Datum(_) => {
+                    let str_id = cached_str_id(name);
+                    quote! {
+                        #convert
+                        target.write_var_id(#str_id?, &#value)?;
+                    }
+                }
+                Source::AssocList => quote! {
+                    #convert
+                    list.write_list_index_internal(
+                        &::byondapi::value::ByondValue::new_str(#name)?,
+                        &#value,
+                    )?;
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(match &input.source {
+        Source::Datum(path) => {
+            let to_byond = path.as_ref().map(|path| {
+                quote! {
+                    impl #impl_generics ::byondapi::value::conversion::ToByond for #ident #ty_generics #where_clause {
+                        fn to_byond(
+                            &self,
+                        ) -> ::std::result::Result<::byondapi::value::ByondValue, ::byondapi::Error> {
+                            let mut datum = ::byondapi::value::ByondValue::builtin_new(
+                                ::byondapi::value::ByondValue::new_str(#path)?,
+                                &[],
+                            )?;
+                            ::byondapi::value::conversion::WriteVars::write_vars(self, &mut datum)?;
+                            Ok(datum)
+                        }
+                    }
+                }
+            });
+            quote! {
+                impl #impl_generics ::byondapi::value::conversion::WriteVars for #ident #ty_generics #where_clause {
+                    fn write_vars(
+                        &self,
+                        target: &mut ::byondapi::value::ByondValue,
+                    ) -> ::std::result::Result<(), ::byondapi::Error> {
+                        #(#writes)*
+                        Ok(())
+                    }
+                }
+                #to_byond
+            }
+        }
+        Source::AssocList => quote! {
+            impl #impl_generics ::byondapi::value::conversion::ToByond for #ident #ty_generics #where_clause {
+                fn to_byond(
+                    &self,
+                ) -> ::std::result::Result<::byondapi::value::ByondValue, ::byondapi::Error> {
+                    let mut list = ::byondapi::value::ByondValue::new_list()?;
+                    #(#writes)*
+                    Ok(list)
+                }
+            }
+        },
+    })
+}
+
+pub fn into_token_stream(result: syn::Result<TokenStream>) -> proc_macro::TokenStream {
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
+}
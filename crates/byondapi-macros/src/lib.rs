@@ -3,6 +3,8 @@ use proc_macro2::Ident;
 use quote::quote;
 use syn::{spanned::Spanned, Lit};
 
+mod derive;
+
 fn extract_args(a: &syn::FnArg) -> &syn::PatType {
     match a {
         syn::FnArg::Typed(p) => p,
@@ -37,6 +39,53 @@ fn get_docs(input: &syn::ItemFn) -> String {
         .collect::<String>()
 }
 
+/// Arguments of the bind attributes, an optional proc path followed by flags
+struct BindAttr {
+    proc: Option<syn::Lit>,
+    /// Cleared by `no_catch_unwind`, panics then unwind straight into BYOND
+    catch_unwind: bool,
+    /// Set by `async`, the function runs as a background job
+    is_async: bool,
+}
+
+impl syn::parse::Parse for BindAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut attr = BindAttr {
+            proc: None,
+            catch_unwind: true,
+            is_async: false,
+        };
+        if input.peek(syn::Lit) {
+            attr.proc = Some(input.parse()?);
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        while !input.is_empty() {
+            if input.parse::<Option<syn::Token![async]>>()?.is_some() {
+                attr.is_async = true;
+                if !input.is_empty() {
+                    input.parse::<syn::Token![,]>()?;
+                }
+                continue;
+            }
+            let flag = input.parse::<Ident>()?;
+            if flag == "no_catch_unwind" {
+                attr.catch_unwind = false;
+            } else {
+                return Err(syn::Error::new(
+                    flag.span(),
+                    "Expected a proc path, `async` or `no_catch_unwind`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        Ok(attr)
+    }
+}
+
 fn ffi_function_signature(func_name_ffi: Ident) -> proc_macro2::TokenStream {
     quote! {
         #[no_mangle]
@@ -68,51 +117,141 @@ fn get_return(input: &syn::ItemFn) -> Result<&syn::ReturnType, TokenStream> {
 }
 
 fn crash_syntax() -> proc_macro2::TokenStream {
-    if cfg!(feature = "old-crash-workaround") {
-        quote! {
-            let error_string = ::byondapi::value::ByondValue::try_from(error_string).unwrap();
-            ::byondapi::global_call::call_global_id({
-                static STACK_TRACE: ::std::sync::OnceLock<u32> = ::std::sync::OnceLock::new();
-                *STACK_TRACE.get_or_init(|| ::byondapi::byond_string::str_id_of("byondapi_stack_trace")
-                    .expect("byondapi-rs implicitly expects byondapi_stack_trace to exist as a proc for error reporting purposes, this proc doesn't exist!")
-                )
-            }
-            ,&[error_string]).unwrap();
-            ::byondapi::value::ByondValue::null()
-        }
-    } else {
-        quote! {
-            unsafe { ::byondapi::runtime::byond_runtime(error_string) }
-        }
+    quote! {
+        unsafe { ::byondapi::runtime::bind_error(error_string) }
     }
 }
 
-fn get_args_disp(
-    input: &syn::ItemFn,
-) -> (
-    String,
-    syn::punctuated::Punctuated<proc_macro2::TokenStream, syn::Token![,]>,
-) {
+struct BindArgs {
+    names_disp: String,
+    /// `let` statements converting each argument, breaking out of `'bind` on failure
+    unpacker: proc_macro2::TokenStream,
+    idents: Vec<Ident>,
+}
+
+fn get_args_disp(input: &syn::ItemFn) -> Result<BindArgs, TokenStream> {
     let args = &input.sig.inputs;
     let mut arg_names: syn::punctuated::Punctuated<syn::Ident, syn::Token![,]> =
         syn::punctuated::Punctuated::new();
-    let mut proc_arg_unpacker: syn::punctuated::Punctuated<
-        proc_macro2::TokenStream,
-        syn::Token![,],
-    > = syn::punctuated::Punctuated::new();
+    let mut unpacker = proc_macro2::TokenStream::new();
+    let mut idents = Vec::new();
 
     for arg in args.iter().map(extract_args) {
-        if let syn::Pat::Ident(p) = &*arg.pat {
-            arg_names.push(p.ident.clone());
-            let index = arg_names.len() - 1;
-            proc_arg_unpacker.push(quote! {
-                args.get(#index).map(::byondapi::value::ByondValue::clone).unwrap_or_default()
-            });
+        let syn::Pat::Ident(p) = &*arg.pat else {
+            return Err(syn::Error::new(
+                arg.pat.span(),
+                "Bind arguments must be plain identifiers, they become the proc's argument names",
+            )
+            .to_compile_error()
+            .into());
+        };
+        let index = arg_names.len();
+        arg_names.push(p.ident.clone());
+
+        let ty = &arg.ty;
+        let name = p.ident.to_string();
+        let expected = quote!(#ty).to_string().replace(' ', "");
+        let ident = quote::format_ident!("__arg{index}");
+        unpacker.extend(quote! {
+            let #ident: #ty = match ::byondapi::binds::convert_arg(args, #index, #name, #expected) {
+                Ok(val) => val,
+                Err(e) => break 'bind Err(::std::format!("{e}")),
+            };
+        });
+        idents.push(ident);
+    }
+
+    Ok(BindArgs {
+        names_disp: quote!(#arg_names).to_string(),
+        unpacker,
+        idents,
+    })
+}
+
+/// Turns `inner`, a block evaluating to `Result<ByondValue, String>` that can `break 'bind` early,
+/// into the rest of the ffi function. Errors and caught panics become runtimes.
+fn ffi_body(inner: proc_macro2::TokenStream, catch_unwind: bool) -> proc_macro2::TokenStream {
+    let crash_syntax = crash_syntax();
+    let result = if catch_unwind {
+        quote! { ::byondapi::binds::catch_panic(|| 'bind: { #inner }) }
+    } else {
+        quote! { 'bind: { #inner } }
+    };
+    quote! {
+        #[allow(unused_labels)]
+        let result: ::std::result::Result<::byondapi::value::ByondValue, ::std::string::String> =
+            #result;
+        let error_string = match result {
+            Ok(val) => return val,
+            Err(error_string) => error_string,
+        };
+        #crash_syntax
+    }
+}
+
+/// Converts what the bind returned. With `raise_exceptions`, `Err`s holding a DM exception raise
+/// it and return null, see `byondapi::exception` for the error types that are looked into. Others
+/// are formatted with `Display` and dropped before the runtime. The alternate flag gets eyre and
+/// anyhow to include their whole chain.
+fn convert_return(
+    call: proc_macro2::TokenStream,
+    raise_exceptions: bool,
+) -> proc_macro2::TokenStream {
+    // Only the wrapper procs throw raised exceptions, a #define would return null and lose it
+    let raise = raise_exceptions.then(|| {
+        quote! {
+            Err(e) if {
+                // Picks the most specific impl for the error's type, see `BindError`
+                #[allow(unused_imports)]
+                use ::byondapi::exception::{RaiseChain as _, RaiseExact as _, RaiseNothing as _};
+                (&&::byondapi::exception::BindError(&e)).raise_bind_error()
+            } => {
+                Ok(::byondapi::value::ByondValue::null())
+            }
+        }
+    });
+    quote! {
+        match #call {
+            Ok(val) => ::byondapi::value::conversion::ToByond::to_byond(&val)
+                .map_err(|e| ::std::format!("Failed to convert return value: {e}")),
+            #raise
+            Err(e) => {
+                let error_string = ::std::format!("{e:#}");
+                ::std::mem::drop(e);
+                Err(error_string)
+            }
         }
     }
+}
 
-    (quote!(#arg_names).to_string(), proc_arg_unpacker)
+/// Body of the ffi function for binds with typed arguments, async ones return the job id
+fn typed_ffi_body(
+    func_name: &Ident,
+    bind_args: &BindArgs,
+    catch_unwind: bool,
+    is_async: bool,
+    raise_exceptions: bool,
+) -> proc_macro2::TokenStream {
+    let unpacker = &bind_args.unpacker;
+    let idents = &bind_args.idents;
+    let call = if is_async {
+        quote! { ::byondapi::jobs::spawn(#func_name(#(#idents),*)) }
+    } else {
+        convert_return(quote! { #func_name(#(#idents),*) }, raise_exceptions)
+    };
+    let body = ffi_body(
+        quote! {
+            #unpacker
+            #call
+        },
+        catch_unwind,
+    );
+    quote! {
+        let args = unsafe { ::byondapi::parse_args(__argc, __argv) };
+        #body
+    }
 }
+
 //this is an example, mr clippy
 #[allow(clippy::test_attr_in_doctest)]
 /// Macro for generating byond binds
@@ -123,8 +262,31 @@ fn get_args_disp(
 /// fn example() {Ok(ByondValue::null())}
 ///
 /// #[byondapi::bind("/datum/example/proc/other_example")]
-/// fn example_other(_: ByondValue, _: ByondValue) {Ok(ByondValue::null())}
+/// fn example_other(src: ByondValue, other: ByondValue) {Ok(ByondValue::null())}
+///
+/// // Arguments can be any type implementing `FromByond`, a bad argument is reported as a runtime
+/// #[byondapi::bind]
+/// fn example_typed(name: String, amount: Option<u32>) {Ok(ByondValue::null())}
 ///
+/// // The Ok value can be anything implementing `ToByond`
+/// #[byondapi::bind]
+/// fn example_return(name: String) {Ok(vec![name.len(), name.chars().count()])}
+///
+/// // An `Exception`, or a `byondapi::Error`, `eyre::Report`, `anyhow::Error` or
+/// // `Box<dyn Error + Send + Sync>` holding one, is thrown as a DM exception. Other errors, like
+/// // your own types wrapping an `Exception`, runtime with their message.
+/// #[byondapi::bind]
+/// fn example_throw(path: String) {Err(byondapi::exception::Exception::new("No save"))}
+///
+/// // Panics are caught and raised as runtimes like errors are, `no_catch_unwind` skips that
+/// // and lets them unwind into BYOND, which takes the server down
+/// #[byondapi::bind("/datum/example/proc/hot", no_catch_unwind)]
+/// fn example_hot(src: ByondValue) {Ok(ByondValue::null())}
+///
+/// // Runs on a job thread, the proc sleeps until it's done. Arguments are converted before
+/// // that, and the Ok value after on the main thread, so both have to be `Send`.
+/// #[byondapi::bind(async)]
+/// async fn example_async(path: String) {Ok(std::fs::read_to_string(path)?)}
 /// ```
 /// Then generate the bindings.dm file with
 /// ```ignore
@@ -138,7 +300,11 @@ fn get_args_disp(
 #[proc_macro_attribute]
 pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(item as syn::ItemFn);
-    let proc = syn::parse_macro_input!(attr as Option<syn::Lit>);
+    let BindAttr {
+        proc,
+        catch_unwind,
+        is_async,
+    } = syn::parse_macro_input!(attr as BindAttr);
 
     let func_name = &input.sig.ident;
     let func_name_disp = quote!(#func_name).to_string();
@@ -149,6 +315,7 @@ pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let args = &input.sig.inputs;
     let body = &input.block;
+    let asyncness = &input.sig.asyncness;
 
     let all_docs = get_docs(&input);
 
@@ -158,9 +325,27 @@ pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
         Err(err) => return err,
     };
 
+    if is_async != asyncness.is_some() {
+        return syn::Error::new(
+            input.sig.fn_token.span(),
+            "`#[bind(async)]` and `async fn` go together",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let function_type = if is_async {
+        quote!(::byondapi::binds::FunctionType::Async)
+    } else {
+        quote!(::byondapi::binds::FunctionType::Default)
+    };
+
     let signature = ffi_function_signature(func_name_ffi);
 
-    let (arg_names_disp, proc_arg_unpacker) = get_args_disp(&input);
+    let bind_args = match get_args_disp(&input) {
+        Ok(bind_args) => bind_args,
+        Err(err) => return err,
+    };
+    let arg_names_disp = &bind_args.names_disp;
 
     //Submit to inventory
     let cthook_prelude = match &proc {
@@ -172,7 +357,7 @@ pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
                         func_name: #func_name_ffi_disp,
                         func_arguments: #arg_names_disp,
                         docs: #all_docs,
-                        function_type: ::byondapi::binds::FunctionType::Default,
+                        function_type: #function_type,
                     }
                 });
             }
@@ -195,30 +380,21 @@ pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
                         func_name: #func_name_ffi_disp,
                         func_arguments: #arg_names_disp,
                         docs: #all_docs,
-                        function_type: ::byondapi::binds::FunctionType::Default,
+                        function_type: #function_type,
                     }
                 });
             }
         }
     };
 
-    let crash_syntax = crash_syntax();
+    let ffi_body = typed_ffi_body(func_name, &bind_args, catch_unwind, is_async, true);
 
     let result = quote! {
         #cthook_prelude
         #signature {
-            let args = unsafe { ::byondapi::parse_args(__argc, __argv) };
-            match #func_name(#proc_arg_unpacker) {
-                Ok(val) => val,
-                Err(e) => {
-                    let error_string = ::std::format!("{e:?}");
-                    ::std::mem::drop(e);
-                    #crash_syntax
-                }
-            }
-
+            #ffi_body
         }
-        fn #func_name(#args) #func_return
+        #asyncness fn #func_name(#args) #func_return
         #body
     };
     result.into()
@@ -229,7 +405,19 @@ pub fn bind(attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn bind_raw_args(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(item as syn::ItemFn);
-    let proc = syn::parse_macro_input!(attr as Option<syn::Lit>);
+    let BindAttr {
+        proc,
+        catch_unwind,
+        is_async,
+    } = syn::parse_macro_input!(attr as BindAttr);
+    if is_async {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Only `#[bind]` can be async",
+        )
+        .to_compile_error()
+        .into();
+    }
 
     let func_name = &input.sig.ident;
     let func_name_disp = quote!(#func_name).to_string();
@@ -299,20 +487,16 @@ pub fn bind_raw_args(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
-    let crash_syntax = crash_syntax();
+    let ffi_body = ffi_body(
+        convert_return(quote! { #func_name(args) }, true),
+        catch_unwind,
+    );
 
     let result = quote! {
         #cthook_prelude
         #signature {
-            let mut args = unsafe { ::byondapi::parse_args(__argc, __argv) };
-            match #func_name(args) {
-                Ok(val) => val,
-                Err(e) => {
-                    let error_string = ::std::format!("{e:?}");
-                    ::std::mem::drop(e);
-                    #crash_syntax
-                }
-            }
+            let args = unsafe { ::byondapi::parse_args(__argc, __argv) };
+            #ffi_body
         }
         fn #func_name(args: &mut [::byondapi::value::ByondValue]) #func_return
         #body
@@ -325,7 +509,19 @@ pub fn bind_raw_args(attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn bind_macro(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(item as syn::ItemFn);
-    let proc = syn::parse_macro_input!(attr as Option<syn::Lit>);
+    let BindAttr {
+        proc,
+        catch_unwind,
+        is_async,
+    } = syn::parse_macro_input!(attr as BindAttr);
+    if is_async {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Only `#[bind]` can be async",
+        )
+        .to_compile_error()
+        .into();
+    }
 
     let func_name = &input.sig.ident;
     let func_name_disp = quote!(#func_name).to_string();
@@ -347,7 +543,11 @@ pub fn bind_macro(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let signature = ffi_function_signature(func_name_ffi);
 
-    let (arg_names_disp, proc_arg_unpacker) = get_args_disp(&input);
+    let bind_args = match get_args_disp(&input) {
+        Ok(bind_args) => bind_args,
+        Err(err) => return err,
+    };
+    let arg_names_disp = &bind_args.names_disp;
 
     //Submit to inventory
     let cthook_prelude = match &proc {
@@ -375,21 +575,12 @@ pub fn bind_macro(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
-    let crash_syntax = crash_syntax();
+    let ffi_body = typed_ffi_body(func_name, &bind_args, catch_unwind, false, false);
 
     let result = quote! {
         #cthook_prelude
         #signature {
-            let args = unsafe { ::byondapi::parse_args(__argc, __argv) };
-            match #func_name(#proc_arg_unpacker) {
-                Ok(val) => val,
-                Err(e) => {
-                    let error_string = ::std::format!("{e:?}");
-                    ::std::mem::drop(e);
-                    #crash_syntax
-                }
-            }
-
+            #ffi_body
         }
         fn #func_name(#args) #func_return
         #body
@@ -407,3 +598,35 @@ pub fn init(_: TokenStream, item: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// Reads a struct from the vars of a datum, or from the keys of an assoc list with
+/// `#[byond(assoc_list)]`. Field names are used as var names unless renamed.
+/// ```ignore
+/// #[derive(FromByond, ToByond)]
+/// #[byond(path = "/datum/reagent")]
+/// struct Reagent {
+///     name: String,
+///     #[byond(rename = "volume")]
+///     amount: f32,
+///     /// Missing or null vars become `Default::default()`
+///     #[byond(default)]
+///     color: Option<String>,
+///     /// Not read or written at all
+///     #[byond(skip)]
+///     cached: u32,
+/// }
+/// ```
+#[proc_macro_derive(FromByond, attributes(byond))]
+pub fn derive_from_byond(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    derive::into_token_stream(derive::from_byond(input))
+}
+
+/// Writes a struct back to the vars of a datum with `WriteVars`, or into a new assoc list with
+/// `#[byond(assoc_list)]`. Datum structs only implement `ToByond` when given a
+/// `#[byond(path = "..")]` to create, see [`FromByond`] for the attributes.
+#[proc_macro_derive(ToByond, attributes(byond))]
+pub fn derive_to_byond(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    derive::into_token_stream(derive::to_byond(input))
+}
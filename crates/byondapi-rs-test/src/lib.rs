@@ -63,6 +63,14 @@ fn test_readwrite_var(object: ByondValue) -> Result<ByondValue> {
     Ok(object.read_string("name")?.try_into()?)
 }
 
+///Tests returning a string converted by ToByond
+#[byondapi::bind]
+fn test_return_string(object: ByondValue) -> Result<String> {
+    setup_panic_handler();
+
+    Ok(object.read_string("name")?)
+}
+
 ///Tests list pushes
 #[byondapi::bind]
 fn test_list_push(mut list: ByondValue) -> Result<ByondValue> {
@@ -86,6 +94,14 @@ fn test_list_double(list: ByondValue) -> Result<ByondValue> {
     Ok(collection.as_slice().try_into()?)
 }
 
+///Tests returning a list converted by ToByond
+#[byondapi::bind]
+fn test_return_list(list: Vec<f32>) -> Result<Vec<f32>> {
+    setup_panic_handler();
+
+    Ok(list.into_iter().map(|num| num * 2.).collect())
+}
+
 ///Tests lists indexing
 #[byondapi::bind]
 fn test_list_index(list: ByondValue) -> Result<ByondValue> {
@@ -1,4 +1,12 @@
-use std::io::Write;
+use std::{
+    any::Any,
+    cell::RefCell,
+    io::Write,
+    panic::{AssertUnwindSafe, Location},
+    sync::{Once, OnceLock},
+};
+
+use crate::{value::conversion::FromByond, value::ByondValue, Error};
 
 pub struct Bind {
     pub proc_path: &'static str,
@@ -11,24 +19,116 @@ pub enum FunctionType {
     Macro,
     Variadic,
     Default,
+    /// `#[bind(async)]`, the proc sleeps until the job is done
+    Async,
 }
 
 inventory::collect!(Bind);
 
+/// Used by the bind macros to convert each argument, missing arguments are treated as null.
+#[doc(hidden)]
+pub fn convert_arg<T: FromByond>(
+    args: &[ByondValue],
+    index: usize,
+    name: &'static str,
+    expected: &'static str,
+) -> Result<T, Error> {
+    let value = args.get(index).copied().unwrap_or_default();
+    T::from_byond(&value).map_err(|source| Error::InvalidArgument {
+        index,
+        name,
+        expected,
+        source: Box::new(source),
+    })
+}
+
+thread_local! {
+    /// Where the last panic on this thread happened, recorded by the hook [`catch_panic`] installs
+    static PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Used by the bind macros to turn panics into errors. The panic message and location make up
+/// the error, the previous panic hook still runs.
+#[doc(hidden)]
+pub fn catch_panic<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let location = info.location().map(Location::to_string);
+            PANIC_LOCATION.with_borrow_mut(|last| *last = location);
+            previous(info)
+        }))
+    });
+
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            // The mock's runtimes have to reach the test like real ones would
+            #[cfg(feature = "mock")]
+            let payload = match payload.downcast::<crate::mock::Runtime>() {
+                Ok(runtime) => std::panic::resume_unwind(runtime),
+                Err(payload) => payload,
+            };
+            let message = panic_message(payload.as_ref());
+            match PANIC_LOCATION.with_borrow_mut(Option::take) {
+                Some(location) => Err(format!("Rust panicked at {location}: {message}")),
+                None => Err(format!("Rust panicked: {message}")),
+            }
+        }
+    }
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+static LIBNAME: OnceLock<String> = OnceLock::new();
+
+/// The libname [`generate_bindings`] was called with, known once the generated `bindings.dm` has
+/// loaded the library. The generated procs byondapi-rs calls itself are prefixed with it.
+pub fn libname() -> Option<&'static str> {
+    LIBNAME.get().map(String::as_str)
+}
+
+/// Name of the global proc [`generate_bindings`] writes as `{libname}_{name}`, or
+/// `byondapi_{name}` if the library doesn't know its libname
+pub(crate) fn generated_proc(name: &str) -> String {
+    format!("{}_{name}", libname().unwrap_or("byondapi"))
+}
+
+/// Called by `__detect_{libname}()` in the generated bindings to tell the library its libname
+///
+/// # Safety
+/// Only to be called by BYOND
+#[no_mangle]
+pub unsafe extern "C-unwind" fn byondapi_set_libname(
+    argc: byondapi_sys::u4c,
+    argv: *mut ByondValue,
+) -> ByondValue {
+    let args = unsafe { crate::parse_args(argc, argv) };
+    if let Some(name) = args.first().and_then(|name| name.get_string().ok()) {
+        _ = LIBNAME.set(name);
+    }
+    ByondValue::null()
+}
+
 pub fn generate_bindings(libname: &str) {
     _ = std::fs::remove_file("./bindings.dm");
     let mut file = std::fs::File::create("./bindings.dm").unwrap();
     let libname_upper = libname.to_uppercase();
+    // 515 has no load_ext
+    let call_ext_only = cfg!(feature = "byond-515-1621");
 
-    let byond_compat_crash = {
-        if cfg!(feature = "byond-515-1621") {
-            "
-/proc/byondapi_stack_trace(msg)
-	CRASH(msg)"
-        } else {
-            ""
-        }
-    };
     file.write_fmt(format_args!(
         "//THIS FILE IS AUTOMATICALLY GENERATED BY {libname_upper}, PLEASE DO NOT TOUCH IT
 //PROC DEFINITIONS MAY MOVE AROUND, THIS IS NORMAL
@@ -37,15 +137,89 @@ pub fn generate_bindings(libname: &str) {
 
 /proc/__detect_{libname}()
 	if (world.system_type == UNIX)
-		return __{libname} = \"lib{libname}\"
+		__{libname} = \"lib{libname}\"
 	else
-		return __{libname} = \"{libname}\"
+		__{libname} = \"{libname}\"
+	// Tells the library which of the procs below are its own
+	call_ext(__{libname}, \"byond:byondapi_set_libname\")(\"{libname}\")
+	return __{libname}
 
 #define {libname_upper} (__{libname} || __detect_{libname}())
-{byond_compat_crash}
+
+/// Reports bind errors on BYOND versions without Byond_CRASH
+/proc/{libname}_stack_trace(msg)
+	CRASH(msg)
+/// Exception a bind raised, thrown by the proc wrapping it once it returns
+/var/__{libname}_exception
+
+/proc/{libname}_raise(exception)
+	__{libname}_exception = exception
+
+/proc/{libname}_throw()
+	var/exception/exception = __{libname}_exception
+	__{libname}_exception = null
+	throw exception
 "
     ))
     .unwrap();
+    if inventory::iter::<Bind>
+        .into_iter()
+        .any(|bind| matches!(bind.function_type, FunctionType::Async))
+    {
+        let poll = if call_ext_only {
+            format!(r#"call_ext({libname_upper}, "byond:byondapi_poll_job")"#)
+        } else {
+            file.write_fmt(format_args!(
+                r#"var/static/__loaded_{libname}_poll_job = load_ext({libname_upper}, "byond:byondapi_poll_job")
+"#
+            ))
+            .unwrap();
+            format!("call_ext(__loaded_{libname}_poll_job)")
+        };
+        file.write_fmt(format_args!(
+            r#"
+/// Sleeps until the job an async bind started is done, then returns its result
+/proc/__await_{libname}(job)
+	while(TRUE)
+		var/list/result = {poll}(job)
+		if(result)
+			return result[1]
+		sleep(world.tick_lag)
+
+"#
+        ))
+        .unwrap();
+    }
+    if cfg!(feature = "ref-tracking") {
+        file.write_fmt(format_args!(
+            r#"
+/// Lists the persistent refs {libname} made that are still alive, grouped by where they were made
+/proc/{libname}_outstanding_refs()
+	return call_ext({libname_upper}, "byond:byondapi_outstanding_refs")()
+
+"#
+        ))
+        .unwrap();
+    }
+    if cfg!(any(feature = "log", feature = "tracing")) {
+        file.write_fmt(format_args!(
+            r#"
+/// Where {libname}'s log records go unless {libname}_set_log_proc() says otherwise
+/proc/{libname}_log(level, target, message)
+	world.log << "\[[level]\] [target]: [message]"
+
+/// Sets the most detailed level {libname} logs, one of "off", "error", "warn", "info", "debug" or "trace"
+/proc/{libname}_set_log_level(level)
+	return call_ext({libname_upper}, "byond:byondapi_set_log_level")(level)
+
+/// Sends {libname}'s log records to the global proc called proc_name, with the level, target and message, or back to world.log if null
+/proc/{libname}_set_log_proc(proc_name)
+	return call_ext({libname_upper}, "byond:byondapi_set_log_proc")(proc_name)
+
+"#
+        ))
+        .unwrap();
+    }
     for thing in inventory::iter::<Bind> {
         let path = thing.proc_path;
         let docs = thing.docs;
@@ -58,7 +232,7 @@ pub fn generate_bindings(libname: &str) {
         match thing.function_type {
             FunctionType::Macro => {
                 let func_name_libname = func_name.replace("_ffi", &format!("_{libname}"));
-                if cfg!(feature = "byond-515-1621") {
+                if call_ext_only {
                     file.write_fmt(format_args!(
                 r#"{docs}#define {func_name_libname}({func_arguments}) call_ext({libname_upper}, "byond:{func_name}")({func_arguments})
 
@@ -77,12 +251,15 @@ pub fn generate_bindings(libname: &str) {
             }
             FunctionType::Variadic => {
                 //can't directly modify args, fuck you byond
-                if cfg!(feature = "byond-515-1621") {
+                if call_ext_only {
                     file.write_fmt(format_args!(
                         r#"{docs}{path}(...)
 	var/list/args_copy = args.Copy()
 	args_copy.Insert(1, src)
-	return call_ext({libname_upper}, "byond:{func_name}")(arglist(args_copy))
+	__{libname}_exception = null
+	. = call_ext({libname_upper}, "byond:{func_name}")(arglist(args_copy))
+	if(__{libname}_exception)
+		{libname}_throw()
 
 "#
                     ))
@@ -93,7 +270,10 @@ pub fn generate_bindings(libname: &str) {
 	var/list/args_copy = args.Copy()
 	args_copy.Insert(1, src)
 	var/static/loaded = load_ext({libname_upper}, "byond:{func_name}")
-	return call_ext(loaded)(arglist(args_copy))
+	__{libname}_exception = null
+	. = call_ext(loaded)(arglist(args_copy))
+	if(__{libname}_exception)
+		{libname}_throw()
 
 "#
                     ))
@@ -101,10 +281,36 @@ pub fn generate_bindings(libname: &str) {
                 }
             }
             FunctionType::Default => {
-                if cfg!(feature = "byond-515-1621") {
+                if call_ext_only {
+                    file.write_fmt(format_args!(
+                        r#"{docs}{path}({func_arguments_srcless})
+	__{libname}_exception = null
+	. = call_ext({libname_upper}, "byond:{func_name}")({func_arguments})
+	if(__{libname}_exception)
+		{libname}_throw()
+
+"#
+                    ))
+                    .unwrap()
+                } else {
+                    file.write_fmt(format_args!(
+                        r#"{docs}{path}({func_arguments_srcless})
+	var/static/loaded = load_ext({libname_upper}, "byond:{func_name}")
+	__{libname}_exception = null
+	. = call_ext(loaded)({func_arguments})
+	if(__{libname}_exception)
+		{libname}_throw()
+
+"#
+                    ))
+                    .unwrap()
+                }
+            }
+            FunctionType::Async => {
+                if call_ext_only {
                     file.write_fmt(format_args!(
                         r#"{docs}{path}({func_arguments_srcless})
-	return call_ext({libname_upper}, "byond:{func_name}")({func_arguments})
+	return __await_{libname}(call_ext({libname_upper}, "byond:{func_name}")({func_arguments}))
 
 "#
                     ))
@@ -113,7 +319,7 @@ pub fn generate_bindings(libname: &str) {
                     file.write_fmt(format_args!(
                         r#"{docs}{path}({func_arguments_srcless})
 	var/static/loaded = load_ext({libname_upper}, "byond:{func_name}")
-	return call_ext(loaded)({func_arguments})
+	return __await_{libname}(call_ext(loaded)({func_arguments}))
 
 "#
                     ))
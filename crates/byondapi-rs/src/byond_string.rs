@@ -1,7 +1,10 @@
 use crate::prelude::*;
 use crate::static_global::byond;
 use crate::Error;
-use std::ffi::{CStr, CString};
+use std::{
+    ffi::{CStr, CString},
+    sync::OnceLock,
+};
 
 pub fn str_id_of<T: Into<Vec<u8>>>(string: T) -> Result<u4c, Error> {
     let c_string = CString::new(string).unwrap();
@@ -15,3 +18,13 @@ pub fn str_id_of_cstr(string: &CStr) -> Result<u4c, Error> {
     }
     Ok(res)
 }
+
+/// Like [`str_id_of`], but the id is kept in `cache` once the string exists. Unlike
+/// [`crate::byond_string!`] this doesn't panic when the string doesn't exist yet.
+pub fn cached_str_id(cache: &OnceLock<u4c>, string: &str) -> Result<u4c, Error> {
+    if let Some(id) = cache.get() {
+        return Ok(*id);
+    }
+    let id = str_id_of(string)?;
+    Ok(*cache.get_or_init(|| id))
+}
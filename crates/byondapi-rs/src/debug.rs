@@ -0,0 +1,117 @@
+//! Finding leaked persistent references, only available with the `ref-tracking` feature.
+//!
+//! Every [`ByondValue::increment_ref`] made through byondapi-rs, which includes
+//! [`RcByondValue`](crate::value::refcounted::RcByondValue) and
+//! [`PersistentRef`](crate::value::refcounted::PersistentRef), captures a backtrace that's thrown
+//! away again by the matching [`ByondValue::decrement_ref`]. Whatever is left over is what
+//! [`outstanding_refs`] reports. Capturing backtraces is slow, so keep this out of release builds.
+//!
+//! `generate_bindings` also writes a `{libname}_outstanding_refs()` proc returning
+//! [`outstanding_refs_report`], for checking on a running server.
+//!
+//! References the api makes on other threads or in [`thread_sync`](crate::threadsync::thread_sync)
+//! callbacks are persistent too, but aren't seen here unless they're incremented. Let go of those
+//! with [`ByondValue::decrement_untracked_ref`], a `decrement_ref` would throw away the backtrace of
+//! an unrelated increment of the same value.
+use std::{backtrace::Backtrace, collections::HashMap, fmt::Write, sync::Mutex};
+
+use crate::value::ByondValue;
+
+/// Backtraces of the increments not decremented yet, per value
+type Live = HashMap<(u8, u32), Vec<Backtrace>>;
+
+static LIVE: Mutex<Option<Live>> = Mutex::new(None);
+
+fn with_live<R>(f: impl FnOnce(&mut Live) -> R) -> R {
+    let mut live = LIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(live.get_or_insert_with(HashMap::new))
+}
+
+fn key(value: &ByondValue) -> (u8, u32) {
+    // Safety: only refcounted values get here, their data is a ref
+    (value.0.type_, unsafe { value.0.data.ref_ })
+}
+
+pub(crate) fn track_increment(value: &ByondValue) {
+    if !value.is_refcounted() {
+        return;
+    }
+    let backtrace = Backtrace::force_capture();
+    with_live(|live| live.entry(key(value)).or_default().push(backtrace));
+}
+
+pub(crate) fn track_decrement(value: &ByondValue) {
+    if !value.is_refcounted() {
+        return;
+    }
+    with_live(|live| {
+        let key = key(value);
+        if let Some(backtraces) = live.get_mut(&key) {
+            backtraces.pop();
+            if backtraces.is_empty() {
+                live.remove(&key);
+            }
+        }
+    })
+}
+
+/// Persistent references that were made in the same place and are still alive
+#[derive(Debug)]
+pub struct OutstandingRefs {
+    /// Where the references were incremented
+    pub backtrace: String,
+    /// One entry per reference, a value shows up more than once if it was incremented more than
+    /// once there
+    pub values: Vec<ByondValue>,
+}
+
+/// The persistent references made through byondapi-rs that haven't been decremented, grouped by
+/// where they were made, the places holding on to the most first
+pub fn outstanding_refs() -> Vec<OutstandingRefs> {
+    let mut sites: HashMap<String, Vec<ByondValue>> = HashMap::new();
+    with_live(|live| {
+        for (&(type_, ref_), backtraces) in live.iter() {
+            let mut value = ByondValue::null();
+            value.0.type_ = type_;
+            value.0.data.ref_ = ref_;
+            for backtrace in backtraces {
+                sites.entry(backtrace.to_string()).or_default().push(value);
+            }
+        }
+    });
+    let mut refs: Vec<_> = sites
+        .into_iter()
+        .map(|(backtrace, values)| OutstandingRefs { backtrace, values })
+        .collect();
+    refs.sort_by_key(|site| std::cmp::Reverse(site.values.len()));
+    refs
+}
+
+/// [`outstanding_refs`] as text, a count and backtrace for every place
+pub fn outstanding_refs_report() -> String {
+    let refs = outstanding_refs();
+    let total: usize = refs.iter().map(|site| site.values.len()).sum();
+    let mut report = format!("{total} outstanding persistent refs\n");
+    for site in refs {
+        _ = write!(
+            report,
+            "\n{} refs made at:\n{}\n",
+            site.values.len(),
+            site.backtrace
+        );
+    }
+    report
+}
+
+/// Called by the `{libname}_outstanding_refs()` proc `generate_bindings` writes, returns
+/// [`outstanding_refs_report`]
+///
+/// # Safety
+/// Only to be called by BYOND
+#[no_mangle]
+pub unsafe extern "C-unwind" fn byondapi_outstanding_refs(
+    _argc: byondapi_sys::u4c,
+    _argv: *mut ByondValue,
+) -> ByondValue {
+    ByondValue::new_str(outstanding_refs_report()).unwrap_or_default()
+}
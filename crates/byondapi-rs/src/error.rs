@@ -32,6 +32,47 @@ pub enum Error {
     NonExistentString(CString),
     /// Thrown when we know byondland failed to create a string
     UnableToCreateString(CString),
+    /// Thrown by the handles in [`crate::object`] when the value is some other type
+    UnexpectedType {
+        expected: &'static str,
+        found: ByondValue,
+    },
+    /// Thrown by [`crate::list::ByondList`] when an index is past the end of the list
+    IndexOutOfBounds { index: usize, len: usize },
+    /// Thrown by [`crate::list::ByondList::splice`] when the range starts after it ends
+    InvalidRange { start: usize, end: usize },
+    /// Thrown by [`crate::threadsync::thread_sync_typed`] and its handles when the callback panicked
+    ThreadSyncPanicked(String),
+    /// Thrown by [`crate::threadsync::thread_sync_typed`] when BYOND returned without running the
+    /// callback
+    ThreadSyncNotRun,
+    /// Thrown by the bind macros when an argument can't be converted to the parameter's type
+    InvalidArgument {
+        /// Position of the argument, starting from 0
+        index: usize,
+        name: &'static str,
+        /// The parameter's type as written in the bind
+        expected: &'static str,
+        source: Box<Error>,
+    },
+    /// A DM exception to throw, see [`crate::exception`]
+    Exception(Box<crate::exception::Exception>),
+    /// Another error, with a note on what was being done when it happened. Made by
+    /// [`Error::context`], and by the var, proc and global proc functions on their own.
+    /// [`Error::root`] gets the error underneath all of them.
+    Context { context: String, source: Box<Error> },
+    /// Thrown by the serde (de)serializers in [`crate::value::serde`] when the data doesn't fit
+    #[cfg(feature = "serde")]
+    Serde(String),
+    /// Thrown by [`crate::value::ByondValue::to_json_with`] when a list or datum contains itself
+    #[cfg(feature = "json")]
+    JsonCycle(ByondValue),
+    /// Thrown by [`crate::value::ByondValue::to_json_with`] when going deeper than `max_depth`
+    #[cfg(feature = "json")]
+    JsonTooDeep(usize),
+    /// Thrown by [`crate::value::ByondValue::to_json_with`] on refs with [`crate::value::json::RefHandling::Error`]
+    #[cfg(feature = "json")]
+    JsonRefNotAllowed(ByondValue),
 }
 
 impl Error {
@@ -42,6 +83,54 @@ impl Error {
             Self::UnknownByondError
         }
     }
+
+    /// Notes what was being done when this happened, like `"reading var 'name' on /obj/foo"`.
+    /// Each context shows up as a `while ...` line under the error, outermost last.
+    pub fn context<C: Into<String>>(self, context: C) -> Self {
+        Self::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The error without any of the [`Error::Context`] around it, for matching on
+    pub fn root(&self) -> &Error {
+        let mut error = self;
+        while let Self::Context { source, .. } = error {
+            error = source;
+        }
+        error
+    }
+
+    /// The contexts around this error, innermost first
+    pub fn contexts(&self) -> Vec<&str> {
+        let mut contexts = Vec::new();
+        let mut error = self;
+        while let Self::Context { context, source } = error {
+            contexts.push(context.as_str());
+            error = source;
+        }
+        contexts.reverse();
+        contexts
+    }
+}
+
+/// [`Error::context`] for results
+pub trait ResultExt<T> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Error>;
+
+    /// Same as [`ResultExt::context`], but only builds the context if there's an error
+    fn with_context<C: Into<String>, F: FnOnce() -> C>(self, context: F) -> Result<T, Error>;
+}
+
+impl<T> ResultExt<T> for Result<T, Error> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Error> {
+        self.map_err(|e| e.context(context))
+    }
+
+    fn with_context<C: Into<String>, F: FnOnce() -> C>(self, context: F) -> Result<T, Error> {
+        self.map_err(|e| e.context(context()))
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -67,11 +156,63 @@ impl std::fmt::Display for Error {
             Self::UnableToCreateString(string) => {
                 write!(f, "Unable to create string \"{string:#?}\"")
             }
+            Self::UnexpectedType { expected, found } => {
+                write!(f, "Value is not a {expected} {found:?}")
+            }
+            Self::IndexOutOfBounds { index, len } => {
+                write!(
+                    f,
+                    "Index {index} is out of bounds for a list of length {len}"
+                )
+            }
+            Self::InvalidRange { start, end } => {
+                write!(f, "Range starts at {start} but ends at {end}")
+            }
+            Self::ThreadSyncPanicked(message) => write!(f, "Thread sync callback: {message}"),
+            Self::ThreadSyncNotRun => write!(f, "BYOND didn't run the thread sync callback"),
+            Self::InvalidArgument {
+                index,
+                name,
+                expected,
+                source,
+            } => write!(
+                f,
+                "Bad argument #{} ({name}), expected {expected}: {source}",
+                index + 1
+            ),
+            Self::Exception(exception) => write!(f, "{exception}"),
+            Self::Context { .. } => {
+                write!(f, "{}", self.root())?;
+                for context in self.contexts() {
+                    write!(f, "\n    while {context}")?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "serde")]
+            Self::Serde(message) => write!(f, "{message}"),
+            #[cfg(feature = "json")]
+            Self::JsonCycle(val) => write!(f, "Value contains itself {val:?}"),
+            #[cfg(feature = "json")]
+            Self::JsonTooDeep(depth) => write!(f, "Value is nested deeper than {depth}"),
+            #[cfg(feature = "json")]
+            Self::JsonRefNotAllowed(val) => {
+                write!(f, "References can't be turned into json {val:?}")
+            }
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidArgument { source, .. } | Self::Context { source, .. } => {
+                Some(source.as_ref())
+            }
+            Self::Exception(exception) => Some(exception.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct ByondError(pub CString);
@@ -103,9 +244,79 @@ macro_rules! map_byond_error {
 
 /// For extreme cases where we know we're about to crash, we write to a log.txt file in PWD so the user has some idea
 /// what went wrong.
+///
+/// Lines are appended to `byondapi-rs-log.txt`, which is rotated to `byondapi-rs-log.txt.1` and so on once
+/// it gets too big. [`set_log_file`](crash_logging::set_log_file) changes where it goes and how much is kept.
 pub mod crash_logging {
+    use std::{fs, io::Write, path::PathBuf, sync::Mutex};
+
+    /// Where [`log_to_file`] writes and when it rotates
+    #[derive(Debug, Clone)]
+    pub struct LogFile {
+        pub path: PathBuf,
+        /// The file is rotated before a line that would take it past this many bytes
+        pub max_size: u64,
+        /// How many rotated files are kept next to the current one
+        pub keep: usize,
+    }
+
+    impl Default for LogFile {
+        fn default() -> Self {
+            LogFile {
+                path: PathBuf::from("./byondapi-rs-log.txt"),
+                max_size: 1024 * 1024,
+                keep: 3,
+            }
+        }
+    }
+
+    static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+    /// Sets where [`log_to_file`] writes from now on
+    pub fn set_log_file(file: LogFile) {
+        *LOG_FILE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(file);
+    }
+
+    fn rotated(file: &LogFile, index: usize) -> PathBuf {
+        let mut path = file.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(file: &LogFile) {
+        if file.keep == 0 {
+            _ = fs::remove_file(&file.path);
+            return;
+        }
+        _ = fs::remove_file(rotated(file, file.keep));
+        for index in (1..file.keep).rev() {
+            _ = fs::rename(rotated(file, index), rotated(file, index + 1));
+        }
+        _ = fs::rename(&file.path, rotated(file, 1));
+    }
+
     pub fn log_to_file<S: AsRef<str>>(log: S) {
+        // Held while writing so lines from different threads don't interleave
+        let mut guard = LOG_FILE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let file = guard.get_or_insert_with(LogFile::default);
+        let mut line = log.as_ref().to_owned();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        if let Ok(metadata) = fs::metadata(&file.path) {
+            if metadata.len() > 0 && metadata.len() + line.len() as u64 > file.max_size {
+                rotate(file);
+            }
+        }
         // Just drop the error, if we can't write the log then :shrug:
-        let _ = std::fs::write("./byondapi-rs-log.txt", log.as_ref());
+        _ = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file.path)
+            .and_then(|mut log| log.write_all(line.as_bytes()));
     }
 }
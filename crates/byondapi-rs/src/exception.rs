@@ -0,0 +1,202 @@
+//! Throwing DM `/exception`s from binds, so `try`/`catch` in DM gets more than a message.
+//!
+//! A bind that returns an [`Exception`], or an [`Error`] made from one, throws it from the proc
+//! `generate_bindings` wrote for the bind instead of runtiming:
+//! ```ignore
+//! #[byondapi::bind]
+//! fn load_save(path: String) -> Result<ByondValue, Exception> {
+//!     let Ok(data) = std::fs::read(&path) else {
+//!         return Err(Exception::new(format!("No save at {path}"))
+//!             .of_type("/exception/save_missing")
+//!             .var("path", ByondValue::new_str(path).unwrap_or_default()));
+//!     };
+//!     // ...
+//! }
+//! ```
+//! ```dm
+//! try
+//!     load_save("data/player.sav")
+//! catch(var/exception/save_missing/e)
+//!     world.log << "no save at [e.path]"
+//! ```
+//! `eyre::Report`, `anyhow::Error` and `Box<dyn Error + Send + Sync>` are searched for an
+//! [`Exception`] or [`Error`] too, with their contexts left out. Other error types aren't looked
+//! into and runtime with their message.
+//!
+//! The exception is handed to the generated `{libname}_raise` proc, and the wrapper proc throws it
+//! once the bind returns. `#define` binds from `bind_macro` and async binds have no wrapper to
+//! throw from, they runtime with the message like any other error.
+use std::panic::Location;
+
+use crate::{binds::generated_proc, global_call::call_global, value::ByondValue, Error};
+
+/// A DM `/exception` (or subtype) to be thrown, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Exception {
+    /// Type of the exception object, `/exception` unless set with [`Exception::of_type`]
+    pub type_path: String,
+    pub name: String,
+    pub desc: Option<String>,
+    pub file: String,
+    pub line: u32,
+    /// Extra vars set on the exception, the type has to define them
+    pub vars: Vec<(String, ByondValue)>,
+}
+
+impl Exception {
+    /// An `/exception` called `name`, `file` and `line` point at the caller
+    #[track_caller]
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        let location = Location::caller();
+        Exception {
+            type_path: "/exception".to_owned(),
+            name: name.into(),
+            desc: None,
+            file: location.file().to_owned(),
+            line: location.line(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Makes the exception a subtype of `/exception` instead, for `catch` to tell apart
+    pub fn of_type<S: Into<String>>(mut self, type_path: S) -> Self {
+        self.type_path = type_path.into();
+        self
+    }
+
+    pub fn desc<S: Into<String>>(mut self, desc: S) -> Self {
+        self.desc = Some(desc.into());
+        self
+    }
+
+    /// Overrides where the exception says it came from
+    pub fn at<S: Into<String>>(mut self, file: S, line: u32) -> Self {
+        self.file = file.into();
+        self.line = line;
+        self
+    }
+
+    /// Sets the var `name` on the exception to `value`
+    pub fn var<S: Into<String>>(mut self, name: S, value: ByondValue) -> Self {
+        self.vars.push((name.into(), value));
+        self
+    }
+
+    /// Creates the exception object
+    pub fn to_byond(&self) -> Result<ByondValue, Error> {
+        let mut exception =
+            ByondValue::builtin_new(ByondValue::new_str(self.type_path.as_str())?, &[])?;
+        exception.write_var("name", &ByondValue::new_str(self.name.as_str())?)?;
+        if let Some(desc) = &self.desc {
+            exception.write_var("desc", &ByondValue::new_str(desc.as_str())?)?;
+        }
+        exception.write_var("file", &ByondValue::new_str(self.file.as_str())?)?;
+        exception.write_var("line", &ByondValue::new_num(self.line as f32))?;
+        for (name, value) in &self.vars {
+            exception.write_var(name.as_str(), value)?;
+        }
+        Ok(exception)
+    }
+
+    /// Creates the exception and has the proc wrapping the bind that's running throw it once the
+    /// bind returns. Returning [`Err`] with the exception from a `#[bind]` does this already,
+    /// this is for `bind_raw_args` binds or handling the error some other way.
+    pub fn raise(&self) -> Result<(), Error> {
+        call_global(generated_proc("raise"), &[self.to_byond()?])?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Exception {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.desc {
+            Some(desc) => write!(f, "{}: {desc}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl std::error::Error for Exception {}
+
+impl From<Exception> for Error {
+    fn from(exception: Exception) -> Self {
+        Error::Exception(Box::new(exception))
+    }
+}
+
+/// Used by the bind macros to raise the error a bind returned, see [`RaiseExact`]. The trait
+/// that applies depends on the error's type, so the most specific one is found without needing
+/// the type to be `'static`.
+#[doc(hidden)]
+pub struct BindError<'a, E>(pub &'a E);
+
+/// Raises an [`Exception`], or an [`Error`] made from one. The contexts of an [`Error`] are added
+/// to the end of the exception's `desc`. Returns whether the exception was raised, the bind
+/// runtimes with the error otherwise.
+#[doc(hidden)]
+pub trait RaiseExact {
+    fn raise_bind_error(&self) -> bool;
+}
+
+impl RaiseExact for &BindError<'_, Exception> {
+    fn raise_bind_error(&self) -> bool {
+        self.0.raise().is_ok()
+    }
+}
+
+impl RaiseExact for &BindError<'_, Error> {
+    fn raise_bind_error(&self) -> bool {
+        raise_error(self.0)
+    }
+}
+
+/// Raises the first [`Exception`] or [`Error`] in the source chain of errors like
+/// `eyre::Report`, `anyhow::Error` and `Box<dyn Error + Send + Sync>`
+#[doc(hidden)]
+pub trait RaiseChain {
+    fn raise_bind_error(&self) -> bool;
+}
+
+impl<E: AsRef<dyn std::error::Error + Send + Sync + 'static>> RaiseChain for &&BindError<'_, E> {
+    fn raise_bind_error(&self) -> bool {
+        let mut next: Option<&(dyn std::error::Error + 'static)> = Some(self.0.as_ref());
+        while let Some(error) = next {
+            if let Some(error) = error.downcast_ref::<Error>() {
+                return raise_error(error);
+            }
+            if let Some(exception) = error.downcast_ref::<Exception>() {
+                return exception.raise().is_ok();
+            }
+            next = error.source();
+        }
+        false
+    }
+}
+
+/// Every other error runtimes
+#[doc(hidden)]
+pub trait RaiseNothing {
+    fn raise_bind_error(&self) -> bool;
+}
+
+impl<E> RaiseNothing for BindError<'_, E> {
+    fn raise_bind_error(&self) -> bool {
+        false
+    }
+}
+
+fn raise_error(error: &Error) -> bool {
+    let Error::Exception(exception) = error.root() else {
+        return false;
+    };
+    let mut exception = exception.as_ref().clone();
+    let contexts = error.contexts();
+    if !contexts.is_empty() {
+        let mut desc = exception.desc.take().unwrap_or_default();
+        for context in contexts {
+            desc.push_str(&format!("\n    while {context}"));
+        }
+        exception.desc = Some(desc.trim_start().to_owned());
+    }
+    exception.raise().is_ok()
+}
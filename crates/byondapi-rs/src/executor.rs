@@ -0,0 +1,182 @@
+//! Async on top of [`thread_sync`], for background code that needs the main thread now and then.
+//!
+//! From a worker thread, [`byond_main`] runs a closure on the main thread and resolves to what it
+//! returned, so background work can be written as straight-line async code:
+//! ```ignore
+//! #[byondapi::bind(async)]
+//! async fn slow_rename(obj: ByondValue, path: String) -> eyre::Result<()> {
+//!     let name = std::fs::read_to_string(path)?;
+//!     byond_main(move || obj.write_var("name", &ByondValue::new_str(name)?)).await??;
+//!     Ok(())
+//! }
+//! ```
+//! [`spawn`] goes the other way and runs a whole future on the main thread, every wake schedules
+//! the next poll through `Byond_ThreadSync`.
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+};
+
+use crate::{binds::panic_message, threadsync::thread_sync, value::ByondValue};
+
+/// Runs `future` to completion on this thread, parking while it's pending
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+struct Shared<T> {
+    /// Filled by the callback, a panic in it is kept to be resumed by whoever awaits
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`byond_main`]
+pub struct ByondMain<T, F> {
+    callback: Option<F>,
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// Runs `callback` on the main thread with [`thread_sync`] when first polled, resolving to its
+/// result. A panic in the callback is resumed in the task awaiting this.
+pub fn byond_main<T, F>(callback: F) -> ByondMain<T, F>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    ByondMain {
+        callback: Some(callback),
+        shared: Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: None,
+        })),
+    }
+}
+
+// The callback is moved out before being used, it's never pinned
+impl<T, F> Unpin for ByondMain<T, F> {}
+
+impl<T, F> Future for ByondMain<T, F>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(result) = shared.result.take() {
+            return Poll::Ready(result.unwrap_or_else(|panic| std::panic::resume_unwind(panic)));
+        }
+        shared.waker = Some(cx.waker().clone());
+        drop(shared);
+
+        if let Some(callback) = self.callback.take() {
+            let shared = self.shared.clone();
+            thread_sync(
+                move || {
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(callback));
+                    let waker = {
+                        let mut shared = shared.lock().unwrap();
+                        shared.result = Some(result);
+                        shared.waker.take()
+                    };
+                    if let Some(waker) = waker {
+                        waker.wake()
+                    }
+                    ByondValue::null()
+                },
+                false,
+            );
+        }
+        Poll::Pending
+    }
+}
+
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A future [`spawn`]ed on the main thread, waking it schedules a poll with [`thread_sync`]
+struct Task {
+    future: Mutex<Option<BoxedFuture>>,
+    /// Set by wakes and cleared before each poll, so a wake during a poll polls again
+    woken: AtomicBool,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if !self.woken.swap(true, Ordering::AcqRel) {
+            thread_sync(
+                move || {
+                    self.run();
+                    ByondValue::null()
+                },
+                false,
+            );
+        }
+    }
+}
+
+impl Task {
+    fn run(self: Arc<Self>) {
+        // Already being polled further up the stack, that poll sees `woken` and goes again
+        let Ok(mut slot) = self.future.try_lock() else {
+            return;
+        };
+        let waker = Waker::from(self.clone());
+        let mut context = Context::from_waker(&waker);
+        while self.woken.swap(false, Ordering::AcqRel) {
+            let Some(future) = slot.as_mut() else {
+                return;
+            };
+            match std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut context)))
+            {
+                Ok(Poll::Pending) => {}
+                Ok(Poll::Ready(())) => {
+                    *slot = None;
+                }
+                Err(panic) => {
+                    *slot = None;
+                    crate::error::crash_logging::log_to_file(format!(
+                        "Spawned task panicked: {}",
+                        panic_message(panic.as_ref())
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Runs `future` on the main thread, polled through [`thread_sync`] whenever it's woken, so it
+/// can use the api directly. A panic drops the future and is logged to `byondapi-rs-log.txt`.
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task = Arc::new(Task {
+        future: Mutex::new(Some(Box::pin(future))),
+        woken: AtomicBool::new(false),
+    });
+    task.wake();
+}
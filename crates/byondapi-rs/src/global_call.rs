@@ -1,5 +1,7 @@
+use crate::error::ResultExt;
 use crate::prelude::*;
 use crate::static_global::byond;
+use crate::value::functions::str_of_id;
 use crate::Error;
 
 use std::ffi::CString;
@@ -13,19 +15,10 @@ pub fn call_global<T: Into<Vec<u8>>>(name: T, args: &[ByondValue]) -> Result<Byo
 
     let str_id = unsafe { byond().Byond_GetStrId(c_str.as_ptr()) };
     if str_id == crate::sys::u2c::MAX as u32 {
-        return Err(Error::InvalidProc(c_string));
+        let context = format!("calling global proc '{}'", c_str.to_string_lossy());
+        return Err(Error::InvalidProc(c_string).context(context));
     }
-    let ptr = args.as_ptr();
-    let mut new_value = ByondValue::default();
-    unsafe {
-        map_byond_error!(byond().Byond_CallGlobalProcByStrId(
-            str_id,
-            ptr.cast(),
-            args.len() as u32,
-            &mut new_value.0
-        ))?;
-    }
-    Ok(new_value)
+    call_global_id(str_id, args)
 }
 
 /// Calls a global proc by its string id.
@@ -40,7 +33,9 @@ pub fn call_global_id(name: u4c, args: &[ByondValue]) -> Result<ByondValue, Erro
             ptr.cast(),
             args.len() as u32,
             &mut new_value.0
-        ))?;
+        ))
+        .with_context(|| format!("calling global proc '{}'", str_of_id(name)))?;
     }
+    crate::value::temp::track(&new_value);
     Ok(new_value)
 }
@@ -0,0 +1,201 @@
+//! Background jobs started by `#[byondapi::bind(async)]`.
+//!
+//! Calling an async bind converts the arguments on the main thread, queues the future on a pool of
+//! worker threads and hands DM a job id right away. The generated proc sleeps, polling the job with
+//! [`byondapi_poll_job`] every tick. The result stays Rust data until the poll that finds it
+//! finished converts it on the main thread, so the values it makes are ordinary temporary
+//! references. The future can use [`byond_main`](crate::executor::byond_main) for anything that
+//! needs the main thread.
+use std::{
+    any::Any,
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    binds::{catch_panic, panic_message},
+    executor::block_on,
+    runtime::bind_error,
+    value::conversion::ToByond,
+    value::ByondValue,
+    Error,
+};
+
+/// Ids go to DM as numbers, which are only exact up to 2^24
+const MAX_ID: u32 = 1 << 24;
+/// How long a finished job waits to be polled before it's dropped, the proc awaiting it might
+/// have been killed
+const UNPOLLED_EXPIRY: Duration = Duration::from_secs(60);
+
+/// Converts the value of a finished job, called on the main thread
+type Convert = Box<dyn FnOnce() -> Result<ByondValue, Error> + Send>;
+
+enum Job {
+    Running,
+    Finished {
+        result: Result<Convert, String>,
+        at: Instant,
+    },
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u32,
+    jobs: HashMap<u32, Job>,
+}
+
+impl Jobs {
+    /// Reserves the next id that isn't taken by a live job
+    fn reserve(&mut self) -> Option<u32> {
+        if self.jobs.len() >= MAX_ID as usize {
+            return None;
+        }
+        loop {
+            self.next_id = self.next_id % MAX_ID + 1;
+            if let Entry::Vacant(entry) = self.jobs.entry(self.next_id) {
+                entry.insert(Job::Running);
+                return Some(self.next_id);
+            }
+        }
+    }
+
+    fn drop_expired(&mut self) {
+        self.jobs.retain(|_, job| match job {
+            Job::Running => true,
+            Job::Finished { at, .. } => at.elapsed() < UNPOLLED_EXPIRY,
+        });
+    }
+}
+
+static JOBS: Mutex<Option<Jobs>> = Mutex::new(None);
+
+fn with_jobs<R>(f: impl FnOnce(&mut Jobs) -> R) -> R {
+    let mut jobs = JOBS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(jobs.get_or_insert_with(Jobs::default))
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Queue of the worker threads, one per core. Started with the first job.
+fn pool() -> &'static mpsc::Sender<Task> {
+    static POOL: OnceLock<mpsc::Sender<Task>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("byondapi-job-{index}"))
+                .spawn(move || loop {
+                    let task = receiver
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .recv();
+                    match task {
+                        Ok(task) => task(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("Failed to start a job thread");
+        }
+        sender
+    })
+}
+
+/// Queues `future` on the job threads and returns the id of its job, used by `#[bind(async)]`.
+/// Panics in the future fail the job like an `Err` does.
+#[doc(hidden)]
+pub fn spawn<F, T, E>(future: F) -> Result<ByondValue, String>
+where
+    F: Future<Output = Result<T, E>> + Send + 'static,
+    T: ToByond + Send + 'static,
+    E: Display + Send + 'static,
+{
+    let id = with_jobs(|jobs| {
+        jobs.drop_expired();
+        jobs.reserve()
+    })
+    .ok_or_else(|| format!("Too many async jobs, {MAX_ID} are already running"))?;
+    let task = move || {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            catch_panic(|| block_on(future).map_err(|e| format!("{e:#}")))
+        }))
+        .unwrap_or_else(|payload| Err(unwound_message(payload)))
+        .map(|value| Box::new(move || value.to_byond()) as Convert);
+        with_jobs(|jobs| {
+            jobs.jobs.insert(
+                id,
+                Job::Finished {
+                    result,
+                    at: Instant::now(),
+                },
+            )
+        });
+    };
+    if pool().send(Box::new(task)).is_err() {
+        with_jobs(|jobs| jobs.jobs.remove(&id));
+        return Err("The job threads are gone".to_owned());
+    }
+    Ok(ByondValue::new_num(id as f32))
+}
+
+/// Message of a panic [`catch_panic`] passed on, which are the mock's runtimes. Unwinding any
+/// further would take the worker thread down and leave the job running forever.
+fn unwound_message(payload: Box<dyn Any + Send>) -> String {
+    #[cfg(feature = "mock")]
+    if let Some(runtime) = payload.downcast_ref::<crate::mock::Runtime>() {
+        return runtime.0.clone();
+    }
+    format!("Rust panicked: {}", panic_message(payload.as_ref()))
+}
+
+/// Polled by the procs `generate_bindings` writes for async binds. Returns null while the job is
+/// running, `list(result)` once it's done, and runtimes if it failed or doesn't exist. Finished
+/// jobs are forgotten once polled, or after a minute if nothing polls them.
+///
+/// # Safety
+/// Only to be called by BYOND
+#[no_mangle]
+pub unsafe extern "C-unwind" fn byondapi_poll_job(
+    argc: byondapi_sys::u4c,
+    argv: *mut ByondValue,
+) -> ByondValue {
+    let args = unsafe { crate::parse_args(argc, argv) };
+    let id = args
+        .first()
+        .and_then(|id| id.get_number().ok())
+        .unwrap_or(0.) as u32;
+    let finished = with_jobs(|jobs| {
+        jobs.drop_expired();
+        match jobs.jobs.remove(&id) {
+            Some(Job::Running) => {
+                jobs.jobs.insert(id, Job::Running);
+                Some(None)
+            }
+            Some(Job::Finished { result, .. }) => Some(Some(result)),
+            None => None,
+        }
+    });
+    let error_string = match finished {
+        None => format!("No job with id {id}"),
+        Some(None) => return ByondValue::null(),
+        Some(Some(Ok(convert))) => {
+            let list = catch_panic(|| {
+                convert()
+                    .and_then(|value| ByondValue::try_from([value].as_slice()))
+                    .map_err(|e| format!("Failed to convert return value: {e:#}"))
+            });
+            match list {
+                Ok(list) => return list,
+                Err(error_string) => error_string,
+            }
+        }
+        Some(Some(Err(error_string))) => error_string,
+    };
+    unsafe { bind_error(error_string) }
+}
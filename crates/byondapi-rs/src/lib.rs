@@ -13,12 +13,23 @@ pub use inventory;
 
 #[macro_use]
 pub mod error;
+#[cfg(feature = "ref-tracking")]
+pub mod debug;
+pub mod exception;
+pub mod executor;
+pub mod jobs;
+pub mod list;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod logging;
 pub mod map;
-#[cfg(feature = "byond-516-1651")]
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod object;
 pub mod pixloc;
-#[cfg(feature = "byond-516-1651")]
 pub mod runtime;
+pub use byondapi_sys::Feature;
 pub use error::Error;
+pub use static_global::byond;
 
 pub mod binds;
 pub mod byond_string;
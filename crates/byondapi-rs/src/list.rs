@@ -0,0 +1,480 @@
+//! [`ByondList`] and [`ByondAssocList`], lists checked once on creation with a [`Vec`]-like and
+//! a [`HashMap`]-like api.
+//!
+//! Like the rest of the list api all indexes start at zero instead of one like byondland.
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    byond_string,
+    prelude::*,
+    value::conversion::{FromByond, ToByond},
+    Error,
+};
+
+/// A [`ByondValue`] that is known to be a list.
+///
+/// Unlike `list.Add()` and `list.Insert()` in DM, pushing or inserting another list adds it as a
+/// single item instead of adding its contents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByondList(ByondValue);
+
+impl ByondList {
+    /// Creates a new empty list
+    pub fn new() -> Result<Self, Error> {
+        ByondValue::new_list().map(Self)
+    }
+
+    /// The list as a plain [`ByondValue`]
+    pub fn as_value(&self) -> &ByondValue {
+        &self.0
+    }
+
+    pub fn into_value(self) -> ByondValue {
+        self.0
+    }
+
+    fn call(&self, proc: u4c, args: &[ByondValue]) -> Result<ByondValue, Error> {
+        self.0.call_id(proc, args)
+    }
+
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.0.builtin_length()?.get_number()? as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Gets the item at `index`, or [`None`] if it's out of bounds
+    pub fn get(&self, index: usize) -> Result<Option<ByondValue>, Error> {
+        if index >= self.len()? {
+            return Ok(None);
+        }
+        self.0
+            .read_list_index_internal(&byond_index(index))
+            .map(Some)
+    }
+
+    /// Replaces the item at `index`, fails if it's out of bounds
+    pub fn set(&mut self, index: usize, value: ByondValue) -> Result<(), Error> {
+        check_index(index, self.len()?)?;
+        self.0
+            .write_list_index_internal(&byond_index(index), &value)
+    }
+
+    /// Adds `value` to the end of the list
+    pub fn push(&mut self, value: ByondValue) -> Result<(), Error> {
+        let len = self.len()?;
+        self.call(byond_string!("Add"), &[ByondValue::null()])?;
+        self.set(len, value)
+    }
+
+    /// Removes the last item and returns it, or [`None`] if the list is empty
+    pub fn pop(&mut self) -> Result<Option<ByondValue>, Error> {
+        let len = self.len()?;
+        if len == 0 {
+            return Ok(None);
+        }
+        self.remove_at(len - 1).map(Some)
+    }
+
+    /// Inserts `value` at `index`, shifting everything after it. Fails if `index` is past the end.
+    pub fn insert(&mut self, index: usize, value: ByondValue) -> Result<(), Error> {
+        self.insert_all(index, &[value])
+    }
+
+    /// Inserts `values` at `index` in a single `Insert()` call
+    fn insert_all(&mut self, index: usize, values: &[ByondValue]) -> Result<(), Error> {
+        let len = self.len()?;
+        check_index(index, len + 1)?;
+        if values.is_empty() {
+            return Ok(());
+        }
+        let mut args = vec![ByondValue::null(); values.len() + 1];
+        args[0] = byond_index(index);
+        self.call(byond_string!("Insert"), &args)?;
+        for (offset, value) in values.iter().enumerate() {
+            self.set(index + offset, *value)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the item at `index` and returns it, shifting everything after it.
+    /// Fails if `index` is out of bounds.
+    pub fn remove_at(&mut self, index: usize) -> Result<ByondValue, Error> {
+        check_index(index, self.len()?)?;
+        let value = self.0.read_list_index_internal(&byond_index(index))?;
+        self.call(
+            byond_string!("Cut"),
+            &[byond_index(index), byond_index(index + 1)],
+        )?;
+        Ok(value)
+    }
+
+    /// Swaps the items at `a` and `b`, fails if either is out of bounds
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<(), Error> {
+        let len = self.len()?;
+        check_index(a, len)?;
+        check_index(b, len)?;
+        if a != b {
+            self.call(byond_string!("Swap"), &[byond_index(a), byond_index(b)])?;
+        }
+        Ok(())
+    }
+
+    /// Shortens the list to `len` items, does nothing if it's already shorter
+    pub fn truncate(&mut self, len: usize) -> Result<(), Error> {
+        if len < self.len()? {
+            self.call(byond_string!("Cut"), &[byond_index(len), 0.0.into()])?;
+        }
+        Ok(())
+    }
+
+    /// Removes every item
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.call(byond_string!("Cut"), &[])?;
+        Ok(())
+    }
+
+    /// Index of the first item equal to `value`, like `list.Find()`
+    pub fn find(&self, value: &ByondValue) -> Result<Option<usize>, Error> {
+        let found = self.call(byond_string!("Find"), &[*value])?.get_number()? as usize;
+        Ok(found.checked_sub(1))
+    }
+
+    pub fn contains(&self, value: &ByondValue) -> Result<bool, Error> {
+        Ok(self.find(value)?.is_some())
+    }
+
+    /// Pushes every item of `values`, see [`ByondList::push`]. The [`Extend`] impl panics instead
+    /// of returning errors.
+    pub fn extend<I: IntoIterator<Item = ByondValue>>(&mut self, values: I) -> Result<(), Error> {
+        let values = values.into_iter().collect::<Vec<_>>();
+        let len = self.len()?;
+        self.insert_all(len, &values)
+    }
+
+    /// Replaces the items in `range` with `replace_with` and returns the removed items, like
+    /// [`Vec::splice`]. Fails if the range is out of bounds or starts after it ends.
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> Result<Vec<ByondValue>, Error>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = ByondValue>,
+    {
+        let len = self.len()?;
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => len,
+        };
+        if start > end {
+            return Err(Error::InvalidRange { start, end });
+        }
+        if end > len {
+            return Err(Error::IndexOutOfBounds { index: end, len });
+        }
+
+        let items = self.to_vec()?;
+        let removed = items[start..end].to_vec();
+        if start != end {
+            self.call(
+                byond_string!("Cut"),
+                &[byond_index(start), byond_index(end)],
+            )?;
+        }
+        let replace_with = replace_with.into_iter().collect::<Vec<_>>();
+        self.insert_all(start, &replace_with)?;
+        Ok(removed)
+    }
+
+    /// Copies the items out into a [`Vec`]
+    pub fn to_vec(&self) -> Result<Vec<ByondValue>, Error> {
+        self.0.get_list_values()
+    }
+
+    /// Iterates over a copy of the items, so changing the list while iterating is fine
+    pub fn iter(&self) -> Result<std::vec::IntoIter<ByondValue>, Error> {
+        self.to_vec().map(Vec::into_iter)
+    }
+}
+
+fn check_index(index: usize, len: usize) -> Result<(), Error> {
+    if index >= len {
+        return Err(Error::IndexOutOfBounds { index, len });
+    }
+    Ok(())
+}
+
+/// DM list indexes start at one
+fn byond_index(index: usize) -> ByondValue {
+    ByondValue::new_num((index + 1) as f32)
+}
+
+impl TryFrom<ByondValue> for ByondList {
+    type Error = Error;
+
+    fn try_from(value: ByondValue) -> Result<Self, Self::Error> {
+        if !value.is_list() {
+            return Err(Error::NotAList(value));
+        }
+        Ok(Self(value))
+    }
+}
+
+impl From<ByondList> for ByondValue {
+    fn from(list: ByondList) -> Self {
+        list.0
+    }
+}
+
+impl FromByond for ByondList {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        Self::try_from(*value)
+    }
+}
+
+impl ToByond for ByondList {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(self.0)
+    }
+}
+
+/// Panics if the list can't be created
+impl FromIterator<ByondValue> for ByondList {
+    fn from_iter<I: IntoIterator<Item = ByondValue>>(iter: I) -> Self {
+        let items = iter.into_iter().collect::<Vec<_>>();
+        ByondValue::try_from(items.as_slice())
+            .map(Self)
+            .expect("failed to create list")
+    }
+}
+
+/// Panics if the items can't be added, use [`ByondList::extend`] to handle errors
+impl Extend<ByondValue> for ByondList {
+    fn extend<I: IntoIterator<Item = ByondValue>>(&mut self, iter: I) {
+        ByondList::extend(self, iter).expect("failed to extend list")
+    }
+}
+
+/// Iterates over a copy of the items, panics if the list can't be read. Use [`ByondList::iter`]
+/// to handle errors.
+impl IntoIterator for ByondList {
+    type Item = ByondValue;
+    type IntoIter = std::vec::IntoIter<ByondValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter().expect("failed to read list")
+    }
+}
+
+impl IntoIterator for &ByondList {
+    type Item = ByondValue;
+    type IntoIter = std::vec::IntoIter<ByondValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter().expect("failed to read list")
+    }
+}
+
+/// A [`ByondValue`] that is known to be a list, viewed as a map from keys to associated values.
+///
+/// Keys can be anything but numbers, since `list[number]` is an index in DM. Reads of more
+/// than one entry take a single snapshot of the whole list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByondAssocList(ByondValue);
+
+impl ByondAssocList {
+    /// Creates a new empty list
+    pub fn new() -> Result<Self, Error> {
+        ByondValue::new_list().map(Self)
+    }
+
+    /// The list as a plain [`ByondValue`]
+    pub fn as_value(&self) -> &ByondValue {
+        &self.0
+    }
+
+    pub fn into_value(self) -> ByondValue {
+        self.0
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.0.builtin_length()?.get_number()? as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Gets the value associated with `key`, or [`None`] if the key isn't in the list
+    pub fn get<K: ToByond>(&self, key: K) -> Result<Option<ByondValue>, Error> {
+        let key = assoc_key(&key)?;
+        let value = self.0.read_list_index_internal(&key)?;
+        // Missing keys read as null too, only look the key up when it matters
+        if value.is_null() && !self.contains_key(key)? {
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
+    /// Associates `value` with `key`, adding the key if it's not in the list yet.
+    /// Returns the previously associated value.
+    pub fn insert<K: ToByond>(
+        &mut self,
+        key: K,
+        value: ByondValue,
+    ) -> Result<Option<ByondValue>, Error> {
+        let key = assoc_key(&key)?;
+        let previous = self.get(key)?;
+        self.0.write_list_index_internal(&key, &value)?;
+        Ok(previous)
+    }
+
+    /// Removes `key` and returns the value that was associated with it
+    pub fn remove<K: ToByond>(&mut self, key: K) -> Result<Option<ByondValue>, Error> {
+        let key = assoc_key(&key)?;
+        let previous = self.get(key)?;
+        if previous.is_some() {
+            self.0.call_id(byond_string!("Remove"), &[key])?;
+        }
+        Ok(previous)
+    }
+
+    pub fn contains_key<K: ToByond>(&self, key: K) -> Result<bool, Error> {
+        let key = assoc_key(&key)?;
+        Ok(self
+            .0
+            .call_id(byond_string!("Find"), &[key])?
+            .get_number()?
+            != 0.0)
+    }
+
+    /// Removes every entry
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.0.call_id(byond_string!("Cut"), &[])?;
+        Ok(())
+    }
+
+    /// Copies out every key and associated value
+    pub fn entries(&self) -> Result<Vec<(ByondValue, ByondValue)>, Error> {
+        Ok(self
+            .0
+            .get_list()?
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect())
+    }
+
+    /// Copies out every key
+    pub fn keys(&self) -> Result<Vec<ByondValue>, Error> {
+        Ok(self.0.get_list()?.into_iter().step_by(2).collect())
+    }
+
+    /// Copies out every associated value
+    pub fn values(&self) -> Result<Vec<ByondValue>, Error> {
+        Ok(self.0.get_list()?.into_iter().skip(1).step_by(2).collect())
+    }
+
+    /// Copies the entries into a map keyed by string, like a [`HashMap`] or [`BTreeMap`].
+    /// Fails if any key isn't a string.
+    pub fn to_map<M: FromIterator<(String, ByondValue)>>(&self) -> Result<M, Error> {
+        self.0
+            .get_list()?
+            .chunks_exact(2)
+            .map(|pair| {
+                if !pair[0].is_str() {
+                    return Err(Error::NotAString(pair[0]));
+                }
+                Ok((pair[0].get_string()?, pair[1]))
+            })
+            .collect()
+    }
+
+    /// Creates a new list out of `entries`
+    pub fn from_entries<K, I>(entries: I) -> Result<Self, Error>
+    where
+        K: ToByond,
+        I: IntoIterator<Item = (K, ByondValue)>,
+    {
+        let mut list = Self::new()?;
+        for (key, value) in entries {
+            list.0
+                .write_list_index_internal(&assoc_key(&key)?, &value)?;
+        }
+        Ok(list)
+    }
+}
+
+fn assoc_key<K: ToByond>(key: &K) -> Result<ByondValue, Error> {
+    let key = key.to_byond()?;
+    if key.is_num() {
+        return Err(Error::InvalidConversion);
+    }
+    Ok(key)
+}
+
+impl TryFrom<ByondValue> for ByondAssocList {
+    type Error = Error;
+
+    fn try_from(value: ByondValue) -> Result<Self, Self::Error> {
+        if !value.is_list() {
+            return Err(Error::NotAList(value));
+        }
+        Ok(Self(value))
+    }
+}
+
+impl From<ByondAssocList> for ByondValue {
+    fn from(list: ByondAssocList) -> Self {
+        list.0
+    }
+}
+
+impl From<ByondList> for ByondAssocList {
+    fn from(list: ByondList) -> Self {
+        Self(list.0)
+    }
+}
+
+impl From<ByondAssocList> for ByondList {
+    fn from(list: ByondAssocList) -> Self {
+        Self(list.0)
+    }
+}
+
+impl FromByond for ByondAssocList {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        Self::try_from(*value)
+    }
+}
+
+impl ToByond for ByondAssocList {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(self.0)
+    }
+}
+
+impl<S> TryFrom<&HashMap<String, ByondValue, S>> for ByondAssocList {
+    type Error = Error;
+
+    fn try_from(map: &HashMap<String, ByondValue, S>) -> Result<Self, Self::Error> {
+        Self::from_entries(map.iter().map(|(key, value)| (key, *value)))
+    }
+}
+
+impl TryFrom<&BTreeMap<String, ByondValue>> for ByondAssocList {
+    type Error = Error;
+
+    fn try_from(map: &BTreeMap<String, ByondValue>) -> Result<Self, Self::Error> {
+        Self::from_entries(map.iter().map(|(key, value)| (key, *value)))
+    }
+}
@@ -0,0 +1,310 @@
+//! Sending `log` and `tracing` records to `world.log` or a DM proc, with the `log` and `tracing`
+//! features.
+//!
+//! Records are handed to the main thread with [`thread_sync`], so logging works from any thread.
+//! With the `log` feature [`init_log`] installs the logger, with `tracing` add [`ByondLayer`] to a
+//! subscriber:
+//! ```ignore
+//! #[byondapi::init]
+//! fn init() {
+//!     _ = byondapi::logging::init_log();
+//! }
+//! ```
+//! `generate_bindings` writes the procs to control it from DM:
+//! ```dm
+//! mylib_set_log_level("debug")      // one of "off", "error", "warn", "info", "debug", "trace"
+//! mylib_set_log_proc("/proc/my_log") // called with the level, target and message instead
+//! mylib_set_log_proc(null)           // back to world.log
+//! ```
+//! Before BYOND has called into the library, or if the proc can't be called, records are appended
+//! to the rotating file of [`crash_logging`] instead.
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Mutex,
+};
+
+use crate::{
+    binds::generated_proc, error::crash_logging, global_call::call_global,
+    static_global::byond_loaded, threadsync::thread_sync, value::ByondValue,
+};
+
+/// How detailed a record is, or with [`LogLevel::Off`], which records get through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    const ALL: [LogLevel; 6] = [
+        LogLevel::Off,
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    /// The level called `name`, ignoring case
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| level.as_str().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+/// Global proc records go to, the generated `{libname}_log` when unset
+static PROC: Mutex<Option<String>> = Mutex::new(None);
+
+/// The most detailed level that gets through, [`LogLevel::Info`] unless changed
+pub fn level() -> LogLevel {
+    LogLevel::ALL[LEVEL.load(Ordering::Relaxed) as usize]
+}
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    #[cfg(feature = "log")]
+    ::log::set_max_level(log_filter(level));
+}
+
+/// Whether records at `level` get through
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level <= self::level()
+}
+
+/// Sends records to the global proc `name` instead of `world.log`, or back to `world.log` with
+/// [`None`]. The proc is called with the level, target and message.
+pub fn set_proc(name: Option<String>) {
+    *PROC.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = name;
+}
+
+fn format_line(level: LogLevel, target: &str, message: &str) -> String {
+    format!("[{level}] {target}: {message}")
+}
+
+/// Sends a record to DM if `level` is [`enabled`], what the `log` logger and the `tracing` layer
+/// end up calling
+pub fn log(level: LogLevel, target: &str, message: &str) {
+    if !enabled(level) {
+        return;
+    }
+    if !byond_loaded() {
+        crash_logging::log_to_file(format_line(level, target, message));
+        return;
+    }
+    let target = target.to_owned();
+    let message = message.to_owned();
+    thread_sync(
+        move || {
+            let proc_name = PROC
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+                .unwrap_or_else(|| generated_proc("log"));
+            let sent = (|| {
+                call_global(
+                    proc_name,
+                    &[
+                        ByondValue::new_str(level.as_str())?,
+                        ByondValue::new_str(target.as_str())?,
+                        ByondValue::new_str(message.as_str())?,
+                    ],
+                )
+            })();
+            if let Err(e) = sent {
+                crash_logging::log_to_file(format!(
+                    "{}\n    (not sent to DM: {e})",
+                    format_line(level, &target, &message)
+                ));
+            }
+            ByondValue::null()
+        },
+        false,
+    );
+}
+
+/// Called by the `{libname}_set_log_level(level)` proc `generate_bindings` writes, returns whether
+/// the level was understood
+///
+/// # Safety
+/// Only to be called by BYOND
+#[no_mangle]
+pub unsafe extern "C-unwind" fn byondapi_set_log_level(
+    argc: byondapi_sys::u4c,
+    argv: *mut ByondValue,
+) -> ByondValue {
+    let args = unsafe { crate::parse_args(argc, argv) };
+    let level = args
+        .first()
+        .and_then(|level| level.get_string().ok())
+        .and_then(|level| LogLevel::parse(&level));
+    match level {
+        Some(level) => {
+            set_level(level);
+            ByondValue::new_num(1.)
+        }
+        None => ByondValue::new_num(0.),
+    }
+}
+
+/// Called by the `{libname}_set_log_proc(proc_name)` proc `generate_bindings` writes, anything
+/// but a string goes back to `world.log`
+///
+/// # Safety
+/// Only to be called by BYOND
+#[no_mangle]
+pub unsafe extern "C-unwind" fn byondapi_set_log_proc(
+    argc: byondapi_sys::u4c,
+    argv: *mut ByondValue,
+) -> ByondValue {
+    let args = unsafe { crate::parse_args(argc, argv) };
+    let name = args
+        .first()
+        .and_then(|name| name.get_string().ok())
+        .map(|name| name.trim_start_matches("/proc/").to_owned())
+        .filter(|name| !name.is_empty());
+    set_proc(name);
+    ByondValue::null()
+}
+
+#[cfg(feature = "log")]
+fn log_filter(level: LogLevel) -> ::log::LevelFilter {
+    match level {
+        LogLevel::Off => ::log::LevelFilter::Off,
+        LogLevel::Error => ::log::LevelFilter::Error,
+        LogLevel::Warn => ::log::LevelFilter::Warn,
+        LogLevel::Info => ::log::LevelFilter::Info,
+        LogLevel::Debug => ::log::LevelFilter::Debug,
+        LogLevel::Trace => ::log::LevelFilter::Trace,
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<::log::Level> for LogLevel {
+    fn from(level: ::log::Level) -> Self {
+        match level {
+            ::log::Level::Error => LogLevel::Error,
+            ::log::Level::Warn => LogLevel::Warn,
+            ::log::Level::Info => LogLevel::Info,
+            ::log::Level::Debug => LogLevel::Debug,
+            ::log::Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+/// The `log` logger, installed by [`init_log`]
+#[cfg(feature = "log")]
+pub struct ByondLogger;
+
+#[cfg(feature = "log")]
+impl ::log::Log for ByondLogger {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {
+        enabled(metadata.level().into())
+    }
+
+    fn log(&self, record: &::log::Record) {
+        log(
+            record.level().into(),
+            record.target(),
+            &record.args().to_string(),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Makes [`ByondLogger`] the `log` crate's logger
+#[cfg(feature = "log")]
+pub fn init_log() -> Result<(), ::log::SetLoggerError> {
+    static LOGGER: ByondLogger = ByondLogger;
+    ::log::set_logger(&LOGGER)?;
+    ::log::set_max_level(log_filter(level()));
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+impl From<tracing_core::Level> for LogLevel {
+    fn from(level: tracing_core::Level) -> Self {
+        match level {
+            tracing_core::Level::ERROR => LogLevel::Error,
+            tracing_core::Level::WARN => LogLevel::Warn,
+            tracing_core::Level::INFO => LogLevel::Info,
+            tracing_core::Level::DEBUG => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+/// `tracing` layer sending events to DM, the `message` field first and the others after it.
+/// Events past [`level`] are skipped here rather than disabled, so other layers still get them.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ByondLayer;
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing_core::field::Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &tracing_core::Field, value: &dyn std::fmt::Debug) {
+        use std::fmt::Write;
+        if field.name() == "message" {
+            _ = write!(self.message, "{value:?}");
+        } else {
+            _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &tracing_core::Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S: tracing_core::Subscriber> tracing_subscriber::Layer<S> for ByondLayer {
+    fn on_event(
+        &self,
+        event: &tracing_core::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        visitor.message.push_str(&visitor.fields);
+        let metadata = event.metadata();
+        log(
+            (*metadata.level()).into(),
+            metadata.target(),
+            &visitor.message,
+        );
+    }
+}
@@ -16,6 +16,21 @@ impl ByondXYZ {
     }
 }
 
+// `junk` is padding, only the coordinates count
+impl PartialEq for ByondXYZ {
+    fn eq(&self, other: &Self) -> bool {
+        self.coordinates() == other.coordinates()
+    }
+}
+
+impl Eq for ByondXYZ {}
+
+impl std::hash::Hash for ByondXYZ {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.coordinates().hash(state)
+    }
+}
+
 impl Default for ByondXYZ {
     fn default() -> Self {
         Self(CByondXYZ {
@@ -57,11 +72,13 @@ pub fn byond_block(corner1: ByondXYZ, corner2: ByondXYZ) -> Result<Vec<ByondValu
 
                 // Safety: buffer should be written to at this point
                 unsafe { buff.set_len(len as usize) };
+                crate::value::temp::track_all(buff);
                 Ok(std::mem::take(buff))
             }
             (true, _) => {
                 // Safety: buffer should be written to at this point
                 unsafe { buff.set_len(len as usize) };
+                crate::value::temp::track_all(buff);
                 Ok(std::mem::take(buff))
             }
             (false, 0) => Err(Error::get_last_byond_error()),
@@ -77,6 +94,7 @@ pub fn byond_locatein(needle: &ByondValue, haystack: &ByondValue) -> Result<Byon
     // Safety: needle, haystack, and output must be initialized, we take care of this.
     unsafe { map_byond_error!(byond().Byond_LocateIn(&needle.0, &haystack.0, &mut output.0))? };
 
+    crate::value::temp::track(&output);
     Ok(output)
 }
 
@@ -90,6 +108,7 @@ pub fn byond_locateby(target: &ByondValue) -> Result<ByondValue, Error> {
         map_byond_error!(byond().Byond_LocateIn(&target.0, std::ptr::null(), &mut output.0))?
     };
 
+    crate::value::temp::track(&output);
     Ok(output)
 }
 
@@ -101,6 +120,7 @@ pub fn byond_locatexyz(coords: ByondXYZ) -> Result<ByondValue, Error> {
     // Safety: coords and output must be initialized, we take care of this.
     unsafe { map_byond_error!(byond().Byond_LocateXYZ(&coords.0, &mut output.0))? };
 
+    crate::value::temp::track(&output);
     Ok(output)
 }
 
@@ -0,0 +1,124 @@
+//! Helpers for setting up the in-process fake of byondcore, only available with the `mock` feature.
+//!
+//! With `mock` enabled, every call byondapi-rs makes is served by [`byondapi_sys::mock`] instead
+//! of a running DreamDaemon, so binds can be tested with a plain `cargo test`. Each test thread
+//! gets its own world, so tests don't need to clean up after themselves.
+//!
+//! ```ignore
+//! #[byondapi::bind]
+//! fn get_name(object: ByondValue) -> eyre::Result<ByondValue> {
+//!     Ok(object.call("get_name", &[])?)
+//! }
+//!
+//! #[test]
+//! fn test_get_name() {
+//!     byondapi::mock::register_type("/obj/thing", &[]);
+//!     byondapi::mock::register_proc("/obj", "get_name", |src, _| src.read_var("name"));
+//!     let thing = ByondValue::builtin_new(ByondValue::new_str("/obj/thing").unwrap(), &[]).unwrap();
+//!     let name = byondapi::mock::call_ffi(get_name_ffi, &[thing]).unwrap();
+//!     assert_eq!(name.get_string().unwrap(), "thing");
+//! }
+//! ```
+use std::panic::AssertUnwindSafe;
+
+use byondapi_sys::{mock as sys_mock, CByondValue};
+
+use crate::{prelude::*, Error};
+
+pub use sys_mock::Runtime;
+
+/// Signature of the `*_ffi` functions generated by the bind macros
+pub type FfiFunction = unsafe extern "C-unwind" fn(u4c, *mut ByondValue) -> ByondValue;
+
+fn as_values(args: &[CByondValue]) -> &[ByondValue] {
+    // Safety: ByondValue is repr(transparent) over CByondValue
+    unsafe { std::slice::from_raw_parts(args.as_ptr().cast(), args.len()) }
+}
+
+/// Throws away this thread's world, objects, lists, procs and types are all forgotten.
+pub fn reset() {
+    sys_mock::reset()
+}
+
+/// Changes the BYOND version the fake reports, which decides what
+/// [`byond().supports`](byondapi_sys::ByondApi::supports) answers and so which functions return
+/// [`Error::NotAvailableForThisByondVersion`]. Starts out as 516.1651, or 515.1621 with the
+/// `byond-515-1621` feature.
+pub fn set_version(major: u32, build: u32) {
+    sys_mock::set_version(major, build)
+}
+
+/// Resizes the map, like setting `world.maxx`, `world.maxy` and `world.maxz`.
+/// Turf refs made before the resize become invalid.
+pub fn set_map_size(x: i16, y: i16, z: i16) {
+    sys_mock::set_map_size(x, y, z)
+}
+
+/// Defines a type and the initial values of its vars. Objects created with
+/// [`ByondValue::builtin_new`] get these plus the vars of all parent types.
+pub fn register_type(path: &str, vars: &[(&str, ByondValue)]) {
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (*name, value.0))
+        .collect::<Vec<_>>();
+    sys_mock::register_type(path, &vars)
+}
+
+/// Defines `{type_path}/proc/{name}`, callable on that type and its subtypes. Errors are turned
+/// into a failed call, just like a runtime in a real proc.
+pub fn register_proc<F>(type_path: &str, name: &str, proc: F)
+where
+    F: Fn(ByondValue, &[ByondValue]) -> Result<ByondValue, Error> + 'static,
+{
+    sys_mock::register_proc(type_path, name, move |src, args| {
+        proc(ByondValue(src), as_values(args))
+            .map(ByondValue::into_inner)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Defines `/proc/{name}`, see [`register_proc`].
+pub fn register_global_proc<F>(name: &str, proc: F)
+where
+    F: Fn(&[ByondValue]) -> Result<ByondValue, Error> + 'static,
+{
+    sys_mock::register_global_proc(name, move |args| {
+        proc(as_values(args))
+            .map(ByondValue::into_inner)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Creates a pointer to a fresh variable holding `initial`, like `&var` in DM.
+pub fn new_pointer(initial: ByondValue) -> ByondValuePointer {
+    ByondValuePointer(ByondValue(sys_mock::new_pointer(initial.0)))
+}
+
+/// Deletes an object like `del()` does, every var and list holding it gets cleared. Its ref is
+/// handed to the next thing created, like BYOND reuses refs.
+pub fn delete(target: &ByondValue) {
+    sys_mock::delete(&target.0)
+}
+
+/// Everything temporary references were released for since the last call, see
+/// [`crate::value::temp::TempScope`]. Before 516 that's done with `ByondValue_DecRef` and
+/// doesn't show up here.
+pub fn take_released_temp_refs() -> Vec<ByondValue> {
+    sys_mock::take_released_temp_refs()
+        .into_iter()
+        .map(ByondValue)
+        .collect()
+}
+
+/// Calls a function generated by `#[byondapi::bind]` and friends the way DM's `call_ext` would.
+/// A runtime raised by the bind is returned as the error.
+pub fn call_ffi(func: FfiFunction, args: &[ByondValue]) -> Result<ByondValue, String> {
+    let mut args = args.to_vec();
+    std::panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        func(args.len() as u4c, args.as_mut_ptr())
+    }))
+    .map_err(|payload| match payload.downcast::<Runtime>() {
+        Ok(runtime) => runtime.0,
+        Err(payload) => std::panic::resume_unwind(payload),
+    })
+}
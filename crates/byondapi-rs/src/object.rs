@@ -0,0 +1,317 @@
+//! Typed handles for objects, checked once on creation.
+//!
+//! Each handle derefs to the one above it, `Turf`, `Obj`, `Mob` and `Area` to [`Atom`], and every
+//! handle eventually to [`Datum`] and [`ByondValue`], so everything on those is available too.
+//! They can be used as bind parameters, a value of the wrong type is reported as a bad argument.
+use crate::{
+    byond_string,
+    list::ByondList,
+    map::{byond_locatexyz, byond_xyz, ByondXYZ},
+    prelude::*,
+    value::conversion::{FromByond, ToByond},
+    Error,
+};
+
+macro_rules! handle {
+    ($(#[$attr:meta])* $name:ident($base:ty), $expected:literal, $($type:ident)|+) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        #[repr(transparent)]
+        pub struct $name($base);
+
+        impl $name {
+            /// Whether `value` can be turned into this handle
+            pub fn is(value: &ByondValue) -> bool {
+                matches!(
+                    ValueType::try_from(value.get_type()),
+                    $(Ok(ValueType::$type))|+
+                )
+            }
+
+            pub fn as_value(&self) -> &ByondValue {
+                self
+            }
+
+            pub fn into_value(self) -> ByondValue {
+                *self.as_value()
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = $base;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl TryFrom<ByondValue> for $name {
+            type Error = Error;
+
+            fn try_from(value: ByondValue) -> Result<Self, Self::Error> {
+                if !Self::is(&value) {
+                    return Err(Error::UnexpectedType {
+                        expected: $expected,
+                        found: value,
+                    });
+                }
+                Ok(Self(<$base>::try_from(value)?))
+            }
+        }
+
+        impl From<$name> for ByondValue {
+            fn from(handle: $name) -> Self {
+                handle.into_value()
+            }
+        }
+
+        impl FromByond for $name {
+            fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+                Self::try_from(*value)
+            }
+        }
+
+        impl ToByond for $name {
+            fn to_byond(&self) -> Result<ByondValue, Error> {
+                Ok(self.into_value())
+            }
+        }
+    };
+}
+
+/// Any object with vars, which includes all of the other handles
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(transparent)]
+pub struct Datum(ByondValue);
+
+impl Datum {
+    /// Whether `value` can be turned into this handle
+    pub fn is(value: &ByondValue) -> bool {
+        matches!(
+            ValueType::try_from(value.get_type()),
+            Ok(ValueType::Datum
+                | ValueType::Turf
+                | ValueType::Obj
+                | ValueType::Mob
+                | ValueType::Area
+                | ValueType::Client
+                | ValueType::Image
+                | ValueType::World)
+        )
+    }
+
+    pub fn as_value(&self) -> &ByondValue {
+        &self.0
+    }
+
+    pub fn into_value(self) -> ByondValue {
+        self.0
+    }
+
+    /// The object's type path, like `"/obj/item"`
+    pub fn type_path(&self) -> Result<String, Error> {
+        self.read_var_id(byond_string!("type"))?.get_string()
+    }
+}
+
+impl std::ops::Deref for Datum {
+    type Target = ByondValue;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Datum {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl TryFrom<ByondValue> for Datum {
+    type Error = Error;
+
+    fn try_from(value: ByondValue) -> Result<Self, Self::Error> {
+        if !Self::is(&value) {
+            return Err(Error::UnexpectedType {
+                expected: "datum",
+                found: value,
+            });
+        }
+        Ok(Self(value))
+    }
+}
+
+impl From<Datum> for ByondValue {
+    fn from(handle: Datum) -> Self {
+        handle.0
+    }
+}
+
+impl FromByond for Datum {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        Self::try_from(*value)
+    }
+}
+
+impl ToByond for Datum {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(self.0)
+    }
+}
+
+handle!(
+    /// A turf, obj, mob or area
+    Atom(Datum), "atom", Turf | Obj | Mob | Area
+);
+handle!(Turf(Atom), "turf", Turf);
+handle!(Obj(Atom), "obj", Obj);
+handle!(Mob(Atom), "mob", Mob);
+handle!(Area(Atom), "area", Area);
+handle!(Client(Datum), "client", Client);
+handle!(Image(Datum), "image", Image);
+handle!(
+    /// The `world` object, see [`World::get`]
+    World(Datum), "world", World
+);
+
+/// Reads a var that holds either null or an object of type `T`
+fn read_handle<T: FromByond>(datum: &Datum, var: u4c) -> Result<Option<T>, Error> {
+    Option::<T>::from_byond(&datum.read_var_id(var)?)
+}
+
+impl Atom {
+    /// The atom containing this one, or [`None`] for areas and things in nullspace
+    pub fn loc(&self) -> Result<Option<Atom>, Error> {
+        read_handle(self, byond_string!("loc"))
+    }
+
+    /// Coordinates of the atom, see [`byond_xyz`]
+    pub fn xyz(&self) -> Result<ByondXYZ, Error> {
+        byond_xyz(self)
+    }
+
+    /// The turf this atom is on, like `get_turf()`
+    pub fn turf(&self) -> Result<Option<Turf>, Error> {
+        Turf::at(self.xyz()?)
+    }
+
+    pub fn name(&self) -> Result<String, Error> {
+        self.read_var_id(byond_string!("name"))?.get_string()
+    }
+
+    pub fn contents(&self) -> Result<ByondList, Error> {
+        ByondList::try_from(self.read_var_id(byond_string!("contents"))?)
+    }
+}
+
+impl Turf {
+    /// The turf at `coords`, or [`None`] if it's outside the map, like `locate(x, y, z)`
+    pub fn at(coords: ByondXYZ) -> Result<Option<Turf>, Error> {
+        Option::<Turf>::from_byond(&byond_locatexyz(coords)?)
+    }
+}
+
+/// A turf kept as its coordinates, looked up again whenever it's needed.
+///
+/// Turf refs change when the map is resized, so this is what to hold on to instead of a [`Turf`],
+/// and it can be used as a map key across ticks and threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TurfRef(ByondXYZ);
+
+impl TurfRef {
+    pub fn new(coords: ByondXYZ) -> Self {
+        TurfRef(coords)
+    }
+
+    pub fn coords(&self) -> ByondXYZ {
+        self.0
+    }
+
+    /// The turf at these coordinates now, or [`None`] if they're outside the map
+    pub fn resolve(&self) -> Result<Option<Turf>, Error> {
+        Turf::at(self.0)
+    }
+}
+
+impl From<ByondXYZ> for TurfRef {
+    fn from(coords: ByondXYZ) -> Self {
+        TurfRef(coords)
+    }
+}
+
+impl From<TurfRef> for ByondXYZ {
+    fn from(turf: TurfRef) -> Self {
+        turf.0
+    }
+}
+
+impl TryFrom<Turf> for TurfRef {
+    type Error = Error;
+
+    fn try_from(turf: Turf) -> Result<Self, Self::Error> {
+        Ok(TurfRef(turf.xyz()?))
+    }
+}
+
+impl FromByond for TurfRef {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        TurfRef::try_from(Turf::from_byond(value)?)
+    }
+}
+
+/// Converts to null if the coordinates are outside the map
+impl ToByond for TurfRef {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(self
+            .resolve()?
+            .map(Turf::into_value)
+            .unwrap_or_else(ByondValue::null))
+    }
+}
+
+impl Mob {
+    /// The client controlling this mob, if there is one
+    pub fn client(&self) -> Result<Option<Client>, Error> {
+        read_handle(self, byond_string!("client"))
+    }
+
+    /// The ckey of the player this mob belongs to, if it belongs to one
+    pub fn ckey(&self) -> Result<Option<String>, Error> {
+        Option::<String>::from_byond(&self.read_var_id(byond_string!("ckey"))?)
+    }
+}
+
+impl Client {
+    /// The mob this client is controlling
+    pub fn mob(&self) -> Result<Option<Mob>, Error> {
+        read_handle(self, byond_string!("mob"))
+    }
+
+    pub fn ckey(&self) -> Result<String, Error> {
+        self.read_var_id(byond_string!("ckey"))?.get_string()
+    }
+}
+
+impl World {
+    /// The `world` object
+    pub fn get() -> Self {
+        World(Datum(ByondValue::new_global_ref()))
+    }
+
+    /// The map size, `world.maxx`, `world.maxy` and `world.maxz`
+    pub fn max_xyz(&self) -> Result<ByondXYZ, Error> {
+        let read = |var| -> Result<i16, Error> { i16::from_byond(&self.read_var_id(var)?) };
+        Ok(ByondXYZ::with_coords((
+            read(byond_string!("maxx"))?,
+            read(byond_string!("maxy"))?,
+            read(byond_string!("maxz"))?,
+        )))
+    }
+}
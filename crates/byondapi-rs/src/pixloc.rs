@@ -1,4 +1,4 @@
-use crate::{prelude::ByondValue, static_global::byond, Error};
+use crate::{prelude::ByondValue, static_global::byond, Error, Feature};
 use byondapi_sys::CByondPixLoc;
 
 #[derive(Debug, Clone, Copy)]
@@ -15,8 +15,11 @@ impl Default for ByondPixLoc {
     }
 }
 
-/// Gets pixloc coords of an atom
+/// Gets pixloc coords of an atom, needs [`Feature::PixLoc`]
 pub fn byond_pixloc(src: ByondValue) -> Result<ByondPixLoc, Error> {
+    if !byond().supports(Feature::PixLoc) {
+        return Err(Error::NotAvailableForThisByondVersion);
+    }
     let mut output = ByondPixLoc::default();
 
     unsafe { map_byond_error!(byond().Byond_PixLoc(&src.0, &mut output.0))? }
@@ -24,8 +27,11 @@ pub fn byond_pixloc(src: ByondValue) -> Result<ByondPixLoc, Error> {
     Ok(output)
 }
 
-/// Gets pixloc coords of an atom based on its bounding box
+/// Gets pixloc coords of an atom based on its bounding box, needs [`Feature::PixLoc`]
 pub fn byond_boundpixloc(src: ByondValue, dir: u8) -> Result<ByondPixLoc, Error> {
+    if !byond().supports(Feature::PixLoc) {
+        return Err(Error::NotAvailableForThisByondVersion);
+    }
     let mut output = ByondPixLoc::default();
 
     unsafe { map_byond_error!(byond().Byond_BoundPixLoc(&src.0, dir, &mut output.0))? }
@@ -28,6 +28,8 @@ pub use byondapi_sys::CByondValue as InternalByondValue;
 
 // As well as our own types.
 pub use crate::byond_string;
+pub use crate::list::{ByondAssocList, ByondList};
+pub use crate::value::conversion::{FromByond, ToByond, WriteVars};
 pub use crate::value::pointer::ByondValuePointer;
 pub use crate::value::types::ValueType;
 pub use crate::value::ByondValue;
@@ -1,12 +1,43 @@
-use crate::static_global::byond;
+use crate::{binds::generated_proc, global_call::call_global, static_global::byond};
+use crate::{error::crash_logging, value::ByondValue, Feature};
 use std::ffi::CString;
 
 /// Immediately returns a runtime from this context.
 ///
-/// ## This function will immediately longjump to byond, Drop destructors or catch_unwind will be ignored.
-/// ## Make sure you drop everything before you call this.
+/// # Safety
+/// This function will immediately longjump to byond, Drop destructors or catch_unwind will be ignored.
+/// Make sure you drop everything before you call this.
+///
+/// # Panics
+/// If byond doesn't have [`Feature::Crash`]
 pub unsafe fn byond_runtime<S: Into<Vec<u8>>>(message: S) -> ! {
+    assert!(
+        byond().supports(Feature::Crash),
+        "Byond_CRASH is not available on this version of BYOND"
+    );
     let c_str = CString::new(message.into()).unwrap();
     unsafe { byond().Byond_CRASH(c_str.as_ptr()) };
     unreachable!()
 }
+
+/// Used by the bind macros to report errors. Runtimes with [`byond_runtime`] if it's available,
+/// otherwise hands the message to the `{libname}_stack_trace` proc the generated bindings define
+/// and returns null. If that proc can't be called the error goes to `byondapi-rs-log.txt`.
+///
+/// # Safety
+/// See [`byond_runtime`]
+#[doc(hidden)]
+pub unsafe fn bind_error(message: String) -> ByondValue {
+    if byond().supports(Feature::Crash) {
+        unsafe { byond_runtime(message) }
+    }
+    let stack_trace = generated_proc("stack_trace");
+    let reported = ByondValue::new_str(message.as_str())
+        .and_then(|message| call_global(stack_trace.as_str(), &[message]));
+    if let Err(e) = reported {
+        crash_logging::log_to_file(format!(
+            "{message}\n    (not reported, calling {stack_trace} failed: {e:#})"
+        ));
+    }
+    ByondValue::null()
+}
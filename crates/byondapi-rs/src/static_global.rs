@@ -1,4 +1,4 @@
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", not(feature = "mock")))]
 fn init_lib() -> byondapi_sys::ByondApi {
     for func in inventory::iter::<super::InitFunc> {
         func.0();
@@ -23,7 +23,7 @@ fn init_lib() -> byondapi_sys::ByondApi {
         .expect("Failed to initialize library.")
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
 fn init_lib() -> byondapi_sys::ByondApi {
     for func in inventory::iter::<super::InitFunc> {
         func.0();
@@ -42,6 +42,14 @@ fn init_lib() -> byondapi_sys::ByondApi {
     }
 }
 
+#[cfg(feature = "mock")]
+fn init_lib() -> byondapi_sys::ByondApi {
+    for func in inventory::iter::<super::InitFunc> {
+        func.0();
+    }
+    byondapi_sys::ByondApi::init_mock()
+}
+
 ///Initialises the byond lib, and calls relevant init functions defined by inventory.
 ///Or returns a reference to the lib if already initialised.
 #[inline(always)]
@@ -49,4 +57,10 @@ pub fn byond() -> &'static byondapi_sys::ByondApi {
     BYOND.get_or_init(init_lib)
 }
 
+/// Whether the byond lib has been initialised, i.e. BYOND has called into us and [`byond`] won't panic
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub(crate) fn byond_loaded() -> bool {
+    BYOND.get().is_some()
+}
+
 static BYOND: std::sync::OnceLock<byondapi_sys::ByondApi> = std::sync::OnceLock::new();
@@ -1,7 +1,14 @@
+//! Running code on the main thread from other threads with `Byond_ThreadSync`.
+//!
+//! [`thread_sync_typed`] and [`thread_sync_detached`] take callbacks returning anything and catch
+//! their panics, [`thread_sync`] is the raw version handing a [`ByondValue`] back to BYOND.
+use crate::binds::catch_panic;
 use crate::static_global::byond;
 use crate::value::ByondValue;
+use crate::Error;
 use byondapi_sys::CByondValue;
 use std::os::raw::c_void;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 
 struct CallbackData<F: FnOnce() -> ByondValue + Send> {
     callback: Option<F>,
@@ -11,9 +18,22 @@ extern "C-unwind" fn trampoline<F: FnOnce() -> ByondValue + Send>(
     data: *mut c_void,
 ) -> CByondValue {
     let data = unsafe { Box::from_raw(data as *mut CallbackData<F>) };
-    (data.callback.unwrap())().into_inner()
+    let callback = data.callback.unwrap();
+    match catch_panic(|| Ok(callback())) {
+        Ok(value) => value.into_inner(),
+        Err(message) => {
+            crate::error::crash_logging::log_to_file(format!("Thread sync callback: {message}"));
+            ByondValue::null().into_inner()
+        }
+    }
 }
 
+/// Runs `callback` on the main thread, returning its value if `block` is set and null otherwise.
+/// A panic in the callback is logged to `byondapi-rs-log.txt` and returns null.
+///
+/// The callback is boxed and handed to BYOND, if BYOND never runs it (e.g. the world shuts down
+/// first) it's leaked along with everything it captured. [`thread_sync_detached`] can be
+/// cancelled, which drops the callback.
 pub fn thread_sync<F>(callback: F, block: bool) -> ByondValue
 where
     F: FnOnce() -> ByondValue + Send + 'static,
@@ -25,3 +45,126 @@ where
 
     ByondValue(unsafe { byond().Byond_ThreadSync(Some(trampoline::<F>), data_ptr, block) })
 }
+
+type BoxedCallback<T> = Box<dyn FnOnce() -> T + Send>;
+
+struct Slot<T> {
+    /// Taken out when the callback runs or is cancelled
+    callback: Option<BoxedCallback<T>>,
+    result: Option<Result<T, Error>>,
+}
+
+struct Shared<T> {
+    slot: Mutex<Slot<T>>,
+    done: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Slot<T>> {
+        self.slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn schedule<T, F>(callback: F, block: bool) -> ThreadSyncHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        slot: Mutex::new(Slot {
+            callback: Some(Box::new(callback)),
+            result: None,
+        }),
+        done: Condvar::new(),
+    });
+    // Only this closure is at BYOND's mercy, the callback itself lives in the shared slot
+    let sent = shared.clone();
+    thread_sync(
+        move || {
+            let Some(callback) = sent.lock().callback.take() else {
+                return ByondValue::null();
+            };
+            let result = catch_panic(|| Ok(callback())).map_err(Error::ThreadSyncPanicked);
+            sent.lock().result = Some(result);
+            sent.done.notify_all();
+            ByondValue::null()
+        },
+        block,
+    );
+    ThreadSyncHandle { shared }
+}
+
+/// Runs `callback` on the main thread and waits for its result. A panic in the callback comes
+/// back as [`Error::ThreadSyncPanicked`] instead of unwinding into BYOND.
+///
+/// # Errors
+/// [`Error::ThreadSyncNotRun`] if BYOND returned without running the callback, it's dropped then
+pub fn thread_sync_typed<T, F>(callback: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let handle = schedule(callback, true);
+    match handle.try_take() {
+        Some(result) => result,
+        None => {
+            handle.cancel();
+            Err(Error::ThreadSyncNotRun)
+        }
+    }
+}
+
+/// Schedules `callback` on the main thread without waiting for it, the result can be collected
+/// from the returned handle. A panic in the callback comes back as [`Error::ThreadSyncPanicked`].
+pub fn thread_sync_detached<T, F>(callback: F) -> ThreadSyncHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    schedule(callback, false)
+}
+
+/// The pending result of [`thread_sync_detached`]. Dropping it doesn't stop the callback.
+///
+/// If BYOND never runs the callback, e.g. because the world shuts down first, the callback and the
+/// slot for its result are leaked, whether the handle was dropped or not. [`cancel`](Self::cancel)
+/// drops the callback and everything it captured, only the slot is leaked then.
+pub struct ThreadSyncHandle<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ThreadSyncHandle<T> {
+    /// Whether the callback has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().result.is_some()
+    }
+
+    /// Takes the result if the callback has finished
+    pub fn try_take(&self) -> Option<Result<T, Error>> {
+        self.shared.lock().result.take()
+    }
+
+    /// Blocks until the callback has run and returns its result.
+    ///
+    /// Never call this on the main thread, the callback can't run while it's blocked.
+    pub fn wait(self) -> Result<T, Error> {
+        let mut slot = self.shared.lock();
+        loop {
+            if let Some(result) = slot.result.take() {
+                return result;
+            }
+            slot = self
+                .shared
+                .done
+                .wait(slot)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Drops the callback if it hasn't started yet, returning whether it did
+    pub fn cancel(self) -> bool {
+        self.shared.lock().callback.take().is_some()
+    }
+}
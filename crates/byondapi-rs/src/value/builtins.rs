@@ -24,6 +24,7 @@ impl ByondValue {
                 &mut result.0
             ))?;
         }
+        super::temp::track(&result);
         Ok(result)
     }
     /// Try to create a new byond object, equivalent to byond's new, but takes a list as arguments instead
@@ -38,6 +39,7 @@ impl ByondValue {
         unsafe {
             map_byond_error!(byond().Byond_NewArglist(&value_type.0, &arglist.0, &mut result.0))?;
         }
+        super::temp::track(&result);
         Ok(result)
     }
 }
@@ -73,6 +73,7 @@ impl ByondValue {
 
         unsafe { map_byond_error!(byond().Byond_CreateList(&mut new_self.0))? }
 
+        super::temp::track(&new_self);
         Ok(new_self)
     }
 }
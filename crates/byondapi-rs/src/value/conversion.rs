@@ -1,7 +1,16 @@
-use std::ffi::CString;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    hash::{BuildHasher, Hash},
+};
 
-use super::ByondValue;
-use crate::Error;
+pub use byondapi_macros::{FromByond, ToByond};
+
+use super::{pointer::ByondValuePointer, ByondValue};
+use crate::{
+    map::{byond_xyz, ByondXYZ},
+    Error,
+};
 
 // From Impls
 impl From<bool> for ByondValue {
@@ -109,3 +118,295 @@ impl TryFrom<&[ByondValue]> for ByondValue {
         Ok(res)
     }
 }
+
+/// Types that can be read out of a [`ByondValue`]. Parameters of `#[byondapi::bind]` functions
+/// can be of any type implementing this.
+pub trait FromByond: Sized {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error>;
+}
+
+impl FromByond for ByondValue {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        Ok(*value)
+    }
+}
+
+impl FromByond for f32 {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        value.get_number()
+    }
+}
+
+impl FromByond for f64 {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        value.get_number().map(f64::from)
+    }
+}
+
+macro_rules! from_byond_int {
+    ($($int:ty),*) => {$(
+        /// Fractions are truncated, numbers that don't fit fail to convert
+        impl FromByond for $int {
+            fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+                let num = f64::from(value.get_number()?.trunc());
+                // `MAX as f64` rounds up to the next power of two for 64 bit ints, which is then
+                // the exclusive bound already, for the rest it's exact and needs the + 1
+                if num < <$int>::MIN as f64 || num >= <$int>::MAX as f64 + 1.0 || num.is_nan() {
+                    return Err(Error::InvalidConversion);
+                }
+                Ok(num as $int)
+            }
+        }
+    )*};
+}
+
+from_byond_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+/// Uses DM truthiness, so null and empty strings are false too
+impl FromByond for bool {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        Ok(value.is_true())
+    }
+}
+
+impl FromByond for String {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        if !value.is_str() {
+            return Err(Error::NotAString(*value));
+        }
+        value.get_string()
+    }
+}
+
+impl FromByond for CString {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        if !value.is_str() {
+            return Err(Error::NotAString(*value));
+        }
+        value.get_cstring()
+    }
+}
+
+/// Null becomes [`None`]
+impl<T: FromByond> FromByond for Option<T> {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            T::from_byond(value).map(Some)
+        }
+    }
+}
+
+/// Converts every item of a list, for assoc lists these are the keys
+impl<T: FromByond> FromByond for Vec<T> {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        value.get_list_values()?.iter().map(T::from_byond).collect()
+    }
+}
+
+/// Converts every key and associated value of an assoc list
+impl<K, V, S> FromByond for HashMap<K, V, S>
+where
+    K: FromByond + Eq + Hash,
+    V: FromByond,
+    S: BuildHasher + Default,
+{
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        value
+            .get_list()?
+            .chunks_exact(2)
+            .map(|pair| Ok((K::from_byond(&pair[0])?, V::from_byond(&pair[1])?)))
+            .collect()
+    }
+}
+
+/// Converts every key and associated value of an assoc list
+impl<K, V> FromByond for BTreeMap<K, V>
+where
+    K: FromByond + Ord,
+    V: FromByond,
+{
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        value
+            .get_list()?
+            .chunks_exact(2)
+            .map(|pair| Ok((K::from_byond(&pair[0])?, V::from_byond(&pair[1])?)))
+            .collect()
+    }
+}
+
+impl FromByond for ByondValuePointer {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        ByondValuePointer::new(*value)
+    }
+}
+
+/// Takes the coordinates of an atom, or a list of three numbers
+impl FromByond for ByondXYZ {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        if !value.is_list() {
+            return byond_xyz(value);
+        }
+        match Vec::<i16>::from_byond(value)?.as_slice() {
+            [x, y, z] => Ok(ByondXYZ::with_coords((*x, *y, *z))),
+            _ => Err(Error::InvalidConversion),
+        }
+    }
+}
+
+/// Types that can be written onto the vars of an existing datum, usually through
+/// `#[derive(ToByond)]`.
+pub trait WriteVars {
+    fn write_vars(&self, target: &mut ByondValue) -> Result<(), Error>;
+}
+
+/// Types that can be turned into a [`ByondValue`]. `#[byondapi::bind]` functions can return
+/// `Result<T, E>` for any `T` implementing this.
+pub trait ToByond {
+    fn to_byond(&self) -> Result<ByondValue, Error>;
+}
+
+impl<T: ToByond + ?Sized> ToByond for &T {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        (**self).to_byond()
+    }
+}
+
+impl ToByond for ByondValue {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(*self)
+    }
+}
+
+/// Becomes null
+impl ToByond for () {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(ByondValue::null())
+    }
+}
+
+impl ToByond for bool {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok((*self).into())
+    }
+}
+
+macro_rules! to_byond_num {
+    ($($num:ty),*) => {$(
+        /// BYOND numbers are f32, so precision may be lost
+        impl ToByond for $num {
+            fn to_byond(&self) -> Result<ByondValue, Error> {
+                Ok(ByondValue::new_num(*self as f32))
+            }
+        }
+    )*};
+}
+
+to_byond_num!(f32, f64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl ToByond for str {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        ByondValue::new_str(self)
+    }
+}
+
+impl ToByond for String {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        ByondValue::new_str(self.as_str())
+    }
+}
+
+impl ToByond for CString {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        ByondValue::new_str(self.as_bytes())
+    }
+}
+
+/// [`None`] becomes null
+impl<T: ToByond> ToByond for Option<T> {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        match self {
+            Some(value) => value.to_byond(),
+            None => Ok(ByondValue::null()),
+        }
+    }
+}
+
+impl<T: ToByond> ToByond for [T] {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        let values = self
+            .iter()
+            .map(ToByond::to_byond)
+            .collect::<Result<Vec<_>, _>>()?;
+        ByondValue::try_from(values.as_slice())
+    }
+}
+
+impl<T: ToByond, const N: usize> ToByond for [T; N] {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        self.as_slice().to_byond()
+    }
+}
+
+impl<T: ToByond> ToByond for Vec<T> {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        self.as_slice().to_byond()
+    }
+}
+
+/// Becomes an assoc list
+impl<K: ToByond, V: ToByond, S> ToByond for HashMap<K, V, S> {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        let mut list = ByondValue::new_list()?;
+        for (key, value) in self {
+            list.write_list_index_internal(&key.to_byond()?, &value.to_byond()?)?;
+        }
+        Ok(list)
+    }
+}
+
+/// Becomes an assoc list
+impl<K: ToByond, V: ToByond> ToByond for BTreeMap<K, V> {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        let mut list = ByondValue::new_list()?;
+        for (key, value) in self {
+            list.write_list_index_internal(&key.to_byond()?, &value.to_byond()?)?;
+        }
+        Ok(list)
+    }
+}
+
+macro_rules! to_byond_tuple {
+    ($($name:ident)+) => {
+        /// Becomes a list of the tuple's items
+        impl<$($name: ToByond),+> ToByond for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_byond(&self) -> Result<ByondValue, Error> {
+                let ($($name,)+) = self;
+                ByondValue::try_from([$($name.to_byond()?),+].as_slice())
+            }
+        }
+    };
+}
+
+to_byond_tuple!(A);
+to_byond_tuple!(A B);
+to_byond_tuple!(A B C);
+to_byond_tuple!(A B C D);
+to_byond_tuple!(A B C D E);
+to_byond_tuple!(A B C D E F);
+
+impl ToByond for ByondValuePointer {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        Ok(self.0)
+    }
+}
+
+/// Becomes a list of the three coordinates, use [`crate::map::byond_locatexyz`] for the turf
+impl ToByond for ByondXYZ {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        let (x, y, z) = self.coordinates();
+        (x, y, z).to_byond()
+    }
+}
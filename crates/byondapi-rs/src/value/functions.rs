@@ -1,9 +1,10 @@
-use std::ffi::CString;
+use std::{ffi::CString, sync::OnceLock};
 
 use byondapi_sys::{u4c, ByondValueType, CByondValue};
 
-use super::ByondValue;
-use crate::{static_global::byond, Error};
+use super::types::ValueType;
+use super::{temp, ByondValue};
+use crate::{byond_string::cached_str_id, error::ResultExt, static_global::byond, Error, Feature};
 
 /// # Compatibility with the C++ API
 impl ByondValue {
@@ -114,14 +115,20 @@ impl ByondValue {
         if self.is_null() {
             return Err(Error::UnableToCreateString(c_string));
         }
+        temp::track(self);
         Ok(())
     }
 
     /// Replaces whatever is currently in this value with a string that's pointed to by the stringid
     /// # DO NOT PASS STRINGIDS THAT ARE NOT RETURNED BY [`crate::byond_string::str_id_of`]
-    #[cfg(feature = "byond-516-1651")]
-    pub fn set_strid(&mut self, strid: u4c) {
-        unsafe { byond().ByondValue_SetStrId(&mut self.0, strid) }
+    ///
+    /// Needs [`Feature::SetStrId`]
+    pub fn set_strid(&mut self, strid: u4c) -> Result<(), Error> {
+        if !byond().supports(Feature::SetStrId) {
+            return Err(Error::NotAvailableForThisByondVersion);
+        }
+        unsafe { byond().ByondValue_SetStrId(&mut self.0, strid) };
+        Ok(())
     }
 
     /// Replaces whatever is currently in this value with a ref
@@ -134,18 +141,21 @@ impl ByondValue {
 impl ByondValue {
     /// Read a variable through the ref. Fails if this isn't a ref type.
     pub fn read_var<T: Into<Vec<u8>>>(&self, name: T) -> Result<ByondValue, Error> {
-        if self.is_num() || self.is_str() || self.is_ptr() || self.is_null() || self.is_list() {
-            return Err(Error::NotReferencable(*self));
-        }
         let c_string = CString::new(name).unwrap();
         let c_str = c_string.as_c_str();
+        let context = || self.context("reading var", &c_str.to_string_lossy());
+        if self.is_num() || self.is_str() || self.is_ptr() || self.is_null() || self.is_list() {
+            return Err(Error::NotReferencable(*self)).with_context(context);
+        }
 
         let mut new_value = ByondValue::default();
 
         unsafe {
-            map_byond_error!(byond().Byond_ReadVar(&self.0, c_str.as_ptr(), &mut new_value.0))?;
+            map_byond_error!(byond().Byond_ReadVar(&self.0, c_str.as_ptr(), &mut new_value.0))
+                .with_context(context)?;
         }
 
+        temp::track(&new_value);
         Ok(new_value)
     }
 
@@ -159,6 +169,7 @@ impl ByondValue {
         let c_str = c_string.as_c_str();
 
         unsafe { map_byond_error!(byond().Byond_WriteVar(&self.0, c_str.as_ptr(), &other.0)) }
+            .with_context(|| self.context("writing var", &c_str.to_string_lossy()))
     }
 
     /// Call a proc using self as src. Fails if this isn't a ref type.
@@ -176,9 +187,11 @@ impl ByondValue {
         let c_string = CString::new(name).unwrap();
         let c_str = c_string.as_c_str();
 
+        let context = || self.context("calling proc", &c_str.to_string_lossy());
+
         let str_id = unsafe { byond().Byond_GetStrId(c_str.as_ptr()) };
         if str_id == crate::sys::u2c::MAX as u32 {
-            return Err(Error::InvalidProc(c_string));
+            return Err(Error::InvalidProc(c_string.clone())).with_context(context);
         }
 
         let ptr = args.as_ptr();
@@ -190,31 +203,61 @@ impl ByondValue {
                 ptr as *const byondapi_sys::CByondValue,
                 args.len() as u32,
                 &mut new_value.0
-            ))?;
+            ))
+            .with_context(context)?;
         }
 
+        temp::track(&new_value);
         Ok(new_value)
     }
 }
 
+/// The string with id `id`, for error messages
+pub(crate) fn str_of_id(id: u4c) -> String {
+    let mut value = ByondValue::null();
+    value.0.type_ = ValueType::String as u8;
+    value.0.data.ref_ = id;
+    value.get_string().unwrap_or_else(|_| format!("#{id}"))
+}
+
+impl ByondValue {
+    /// Context for errors about var or proc `name` on this, which names the type if it has one
+    fn context(&self, action: &str, name: &str) -> String {
+        static TYPE: OnceLock<u4c> = OnceLock::new();
+        let mut type_path = ByondValue::null();
+        // Not read_var_id, that would come right back here if it fails
+        let has_type = cached_str_id(&TYPE, "type").is_ok_and(|type_id| unsafe {
+            byond().Byond_ReadVarByStrId(&self.0, type_id, &mut type_path.0)
+        });
+        match type_path.get_string() {
+            Ok(type_path) if has_type => format!("{action} '{name}' on {type_path}"),
+            _ => format!("{action} '{name}' on {self:?}"),
+        }
+    }
+}
+
 /// # Accessors by ids
 impl ByondValue {
     /// Read a variable through the ref. Fails if this isn't a ref type, or the id is invalid.
     pub fn read_var_id(&self, name: u4c) -> Result<ByondValue, Error> {
+        let context = || self.context("reading var", &str_of_id(name));
         if self.is_num() || self.is_str() || self.is_ptr() || self.is_null() || self.is_list() {
-            return Err(Error::NotReferencable(*self));
+            return Err(Error::NotReferencable(*self)).with_context(context);
         }
         let mut new_value = ByondValue::default();
         unsafe {
-            map_byond_error!(byond().Byond_ReadVarByStrId(&self.0, name, &mut new_value.0))?;
+            map_byond_error!(byond().Byond_ReadVarByStrId(&self.0, name, &mut new_value.0))
+                .with_context(context)?;
         }
 
+        temp::track(&new_value);
         Ok(new_value)
     }
 
     /// Write to a variable through the ref. Fails if this isn't a ref type, or the id is invalid.
     pub fn write_var_id(&mut self, name: u4c, other: &ByondValue) -> Result<(), Error> {
         unsafe { map_byond_error!(byond().Byond_WriteVarByStrId(&self.0, name, &other.0)) }
+            .with_context(|| self.context("writing var", &str_of_id(name)))
     }
 
     /// Call a proc using self as src. Fails if this isn't a ref type, or the id is invalid.
@@ -234,9 +277,11 @@ impl ByondValue {
                 ptr as *const byondapi_sys::CByondValue,
                 args.len() as u32,
                 &mut new_value.0
-            ))?;
+            ))
+            .with_context(|| self.context("calling proc", &str_of_id(name)))?;
         }
 
+        temp::track(&new_value);
         Ok(new_value)
     }
 }
@@ -245,16 +290,40 @@ impl ByondValue {
 /// DO NOT USE, use [`super::refcounted::RcByondValue`] instead
 impl ByondValue {
     pub fn increment_ref(&mut self) {
+        #[cfg(feature = "ref-tracking")]
+        crate::debug::track_increment(self);
         unsafe { byond().ByondValue_IncRef(&self.0) }
     }
 
     pub fn decrement_ref(&mut self) {
+        #[cfg(feature = "ref-tracking")]
+        crate::debug::track_decrement(self);
+        unsafe { byond().ByondValue_DecRef(&self.0) }
+    }
+
+    /// [`ByondValue::decrement_ref`] for references that weren't made with
+    /// [`ByondValue::increment_ref`], like the persistent ones values made in
+    /// [`thread_sync`](crate::threadsync::thread_sync) callbacks come with. With the
+    /// `ref-tracking` feature a plain `decrement_ref` would count against an unrelated increment
+    /// of the same value.
+    pub fn decrement_untracked_ref(&mut self) {
         unsafe { byond().ByondValue_DecRef(&self.0) }
     }
 
-    #[cfg(feature = "byond-516-1651")]
+    /// Before [`Feature::DecTempRef`] this is [`ByondValue::decrement_ref`], which let go of
+    /// temporary references back then, but took a persistent reference first if the value had one
     pub fn decrement_tempref(&mut self) {
-        unsafe { byond().ByondValue_DecTempRef(&self.0) }
+        if byond().supports(Feature::DecTempRef) {
+            unsafe { byond().ByondValue_DecTempRef(&self.0) }
+        } else {
+            unsafe { byond().ByondValue_DecRef(&self.0) }
+        }
+    }
+
+    /// Checks with BYOND that this ref still points at something, setting it to null if not.
+    /// A deleted datum's ref id can be reused by a new one, which this can't tell apart.
+    pub fn test_ref(&mut self) -> bool {
+        unsafe { byond().Byond_TestRef(&mut self.0) }
     }
 
     pub fn get_refcount(&self) -> Result<u32, Error> {
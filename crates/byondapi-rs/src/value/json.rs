@@ -0,0 +1,194 @@
+//! Converting between [`ByondValue`] and [`serde_json::Value`] without going through DM's
+//! `json_encode`/`json_decode`, only available with the `json` feature.
+use std::collections::HashSet;
+
+use serde_json::{Map, Number, Value};
+
+use super::ByondValue;
+use crate::Error;
+
+/// What [`ByondValue::to_json_with`] does with references to datums, atoms, clients and such
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RefHandling {
+    /// Writes the ref text, `"[0x2000001]"`, which `locate()` turns back into the object
+    #[default]
+    RefString,
+    /// Writes an object of the datum's vars, excluding `vars` itself. Values that don't have
+    /// vars, like type paths, are written as ref strings.
+    Vars,
+    /// Fails with [`Error::JsonRefNotAllowed`]
+    Error,
+}
+
+/// Options for [`ByondValue::to_json_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonOptions {
+    pub refs: RefHandling,
+    /// How many lists or datums deep to go before failing with [`Error::JsonTooDeep`]
+    pub max_depth: usize,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self {
+            refs: RefHandling::default(),
+            max_depth: 64,
+        }
+    }
+}
+
+impl ByondValue {
+    /// Converts this value to json the way `json_encode` does, with the default [`JsonOptions`].
+    /// Lists with associated values become objects, other lists become arrays.
+    pub fn to_json(&self) -> Result<Value, Error> {
+        self.to_json_with(&JsonOptions::default())
+    }
+
+    /// Converts this value to json, see [`ByondValue::to_json`]
+    pub fn to_json_with(&self, options: &JsonOptions) -> Result<Value, Error> {
+        JsonWriter {
+            options,
+            path: HashSet::new(),
+        }
+        .write(self, 0)
+    }
+
+    /// Converts json to a value the way `json_decode` does. Objects become assoc lists, `true`
+    /// and `false` become 1 and 0.
+    pub fn from_json(json: &Value) -> Result<ByondValue, Error> {
+        Ok(match json {
+            Value::Null => ByondValue::null(),
+            Value::Bool(b) => (*b).into(),
+            Value::Number(num) => ByondValue::new_num(num.as_f64().unwrap_or_default() as f32),
+            Value::String(string) => ByondValue::new_str(string.as_str())?,
+            Value::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(ByondValue::from_json)
+                    .collect::<Result<Vec<_>, _>>()?;
+                ByondValue::try_from(items.as_slice())?
+            }
+            Value::Object(map) => {
+                let mut list = ByondValue::new_list()?;
+                for (key, value) in map {
+                    list.write_list_index_internal(
+                        &ByondValue::new_str(key.as_str())?,
+                        &ByondValue::from_json(value)?,
+                    )?;
+                }
+                list
+            }
+        })
+    }
+
+    /// The text `"\ref[value]"` gives in DM, `"[0x2000001]"`
+    fn ref_string(&self) -> Result<String, Error> {
+        let ref_ = self.get_ref()?;
+        Ok(format!("[0x{:x}]", ((self.get_type() as u32) << 24) | ref_))
+    }
+}
+
+struct JsonWriter<'a> {
+    options: &'a JsonOptions,
+    /// The lists and datums being written right now, seeing one again means a cycle
+    path: HashSet<(u8, u32)>,
+}
+
+impl JsonWriter<'_> {
+    fn write(&mut self, value: &ByondValue, depth: usize) -> Result<Value, Error> {
+        if value.is_null() {
+            return Ok(Value::Null);
+        }
+        if value.is_num() {
+            return Ok(number(value.get_number()?));
+        }
+        if value.is_str() {
+            return Ok(Value::String(value.get_string()?));
+        }
+
+        let vars = if value.is_list() {
+            None
+        } else {
+            match self.options.refs {
+                RefHandling::RefString => return Ok(Value::String(value.ref_string()?)),
+                RefHandling::Error => return Err(Error::JsonRefNotAllowed(*value)),
+                RefHandling::Vars => match value.read_var("vars") {
+                    Ok(vars) => Some(vars),
+                    Err(_) => return Ok(Value::String(value.ref_string()?)),
+                },
+            }
+        };
+
+        if depth >= self.options.max_depth {
+            return Err(Error::JsonTooDeep(self.options.max_depth));
+        }
+        let key = (value.get_type(), value.get_ref()?);
+        if !self.path.insert(key) {
+            return Err(Error::JsonCycle(*value));
+        }
+        let result = match vars {
+            Some(vars) => self.write_vars(&vars, depth),
+            None => self.write_list(value, depth),
+        };
+        self.path.remove(&key);
+        result
+    }
+
+    fn write_list(&mut self, list: &ByondValue, depth: usize) -> Result<Value, Error> {
+        let pairs = list.get_list()?;
+        if pairs.chunks_exact(2).all(|pair| pair[1].is_null()) {
+            return pairs
+                .iter()
+                .step_by(2)
+                .map(|item| self.write(item, depth + 1))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        let mut map = Map::with_capacity(pairs.len() / 2);
+        for pair in pairs.chunks_exact(2) {
+            let key = self.key(&pair[0])?;
+            map.insert(key, self.write(&pair[1], depth + 1)?);
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn write_vars(&mut self, vars: &ByondValue, depth: usize) -> Result<Value, Error> {
+        let mut map = Map::new();
+        for pair in vars.get_list()?.chunks_exact(2) {
+            let name = pair[0].get_string()?;
+            if name == "vars" {
+                continue;
+            }
+            let value = self.write(&pair[1], depth + 1)?;
+            map.insert(name, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Json object keys have to be strings, refs used as keys are written as ref strings
+    fn key(&self, key: &ByondValue) -> Result<String, Error> {
+        if key.is_str() {
+            key.get_string()
+        } else if key.is_num() {
+            Ok(number(key.get_number()?).to_string())
+        } else {
+            key.ref_string()
+        }
+    }
+}
+
+/// Whole numbers are written without a fraction, others with the shortest text that reads back
+/// as the same f32 instead of the widened f64. Infinity and NaN become null like in javascript.
+fn number(num: f32) -> Value {
+    if !num.is_finite() {
+        return Value::Null;
+    }
+    if num.fract() == 0.0 && num.abs() < i64::MAX as f32 {
+        return Value::Number((num as i64).into());
+    }
+    num.to_string()
+        .parse::<f64>()
+        .ok()
+        .and_then(Number::from_f64)
+        .map_or(Value::Null, Value::Number)
+}
@@ -1,89 +1,115 @@
+use std::cell::Cell;
+
 use crate::{byond_string, static_global::byond, value::ByondValue, Error};
+
+thread_local! {
+    /// Buffer lent out by [`ListSnapshot`], kept around so reads don't allocate every time
+    static BUFFER: Cell<Vec<ByondValue>> = Cell::new(Vec::with_capacity(1));
+}
+
+/// A copy of a list's items, read into a buffer that's reused for the next snapshot on this
+/// thread once this is dropped. Derefs to `[ByondValue]`.
+///
+/// Taking a snapshot while another one is alive on the same thread works, but the second one
+/// allocates its own buffer.
+pub struct ListSnapshot {
+    items: Vec<ByondValue>,
+}
+
+impl std::ops::Deref for ListSnapshot {
+    type Target = [ByondValue];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl Drop for ListSnapshot {
+    fn drop(&mut self) {
+        let items = std::mem::take(&mut self.items);
+        // Keep whichever buffer is bigger, if a nested snapshot already put one back
+        _ = BUFFER.try_with(|buffer| {
+            let other = buffer.take();
+            buffer.set(if other.capacity() > items.capacity() {
+                other
+            } else {
+                items
+            });
+        });
+    }
+}
+
 /// List stuff goes here, Keep in mind that all indexing method starts at zero instead of one like byondland
 impl ByondValue {
-    /// Gets an array of all the list values, this means values for assoc lists and just items in the listfor regular lists
-    pub fn get_list_values(&self) -> Result<Vec<ByondValue>, Error> {
-        use std::cell::RefCell;
+    /// Reads the list through `Byond_ReadList` or `Byond_ReadListAssoc` into `buff`, growing it
+    /// if it's too small
+    fn read_list_raw(&self, buff: &mut Vec<ByondValue>, assoc: bool) -> Result<(), Error> {
         if !self.is_list() {
             return Err(Error::NotAList(*self));
         }
-
-        thread_local! {
-            static BUFFER: RefCell<Vec<ByondValue>> = RefCell::new(Vec::with_capacity(1));
-        }
-
-        BUFFER.with_borrow_mut(|buff| -> Result<Vec<ByondValue>, Error> {
+        buff.clear();
+        loop {
             let mut len = buff.capacity() as u32;
-
             // Safety: buffer capacity is passed to byond, which makes sure it writes in-bound
-            let initial_res =
-                unsafe { byond().Byond_ReadList(&self.0, buff.as_mut_ptr().cast(), &mut len) };
-            match (initial_res, len) {
-                (false, 1..) => {
-                    buff.reserve_exact(len as usize);
-                    // Safety: buffer capacity is passed to byond, which makes sure it writes in-bound
-                    unsafe {
-                        map_byond_error!(byond().Byond_ReadList(
-                            &self.0,
-                            buff.as_mut_ptr().cast(),
-                            &mut len
-                        ))?
-                    };
-                    // Safety: buffer should be written to at this point
-                    unsafe { buff.set_len(len as usize) };
-                    Ok(std::mem::take(buff))
+            let res = unsafe {
+                if assoc {
+                    byond().Byond_ReadListAssoc(&self.0, buff.as_mut_ptr().cast(), &mut len)
+                } else {
+                    byond().Byond_ReadList(&self.0, buff.as_mut_ptr().cast(), &mut len)
                 }
+            };
+            match (res, len) {
                 (true, _) => {
                     // Safety: buffer should be written to at this point
                     unsafe { buff.set_len(len as usize) };
-                    Ok(std::mem::take(buff))
+                    super::temp::track_all(buff);
+                    return Ok(());
                 }
-                (false, 0) => Err(Error::get_last_byond_error()),
+                (false, 1..) => buff.reserve_exact(len as usize),
+                (false, 0) => return Err(Error::get_last_byond_error()),
             }
-        })
+        }
+    }
+
+    fn snapshot(&self, assoc: bool) -> Result<ListSnapshot, Error> {
+        let mut snapshot = ListSnapshot {
+            items: BUFFER.take(),
+        };
+        self.read_list_raw(&mut snapshot.items, assoc)?;
+        Ok(snapshot)
+    }
+
+    /// Gets an array of all the list values, this means values for assoc lists and just items in the listfor regular lists
+    pub fn get_list_values(&self) -> Result<Vec<ByondValue>, Error> {
+        Ok(self.list_snapshot()?.to_vec())
     }
 
     /// Gets an array of all the list elements, this means both keys and values for assoc lists and values for regular lists
     /// Reads items as key,value pairs from an associative list, storing them sequentially as key1, value1, key2, value2, etc.
     pub fn get_list(&self) -> Result<Vec<ByondValue>, Error> {
-        use std::cell::RefCell;
-        if !self.is_list() {
-            return Err(Error::NotAList(*self));
-        }
+        Ok(self.list_assoc_snapshot()?.to_vec())
+    }
 
-        thread_local! {
-            static BUFFER: RefCell<Vec<ByondValue>> = RefCell::new(Vec::with_capacity(1));
-        }
+    /// Same as [`ByondValue::get_list_values`], but lends the items out of a per-thread buffer
+    /// instead of allocating
+    pub fn list_snapshot(&self) -> Result<ListSnapshot, Error> {
+        self.snapshot(false)
+    }
 
-        BUFFER.with_borrow_mut(|buff| -> Result<Vec<ByondValue>, Error> {
-            let mut len = buff.capacity() as u32;
+    /// Same as [`ByondValue::get_list`], but lends the items out of a per-thread buffer instead
+    /// of allocating
+    pub fn list_assoc_snapshot(&self) -> Result<ListSnapshot, Error> {
+        self.snapshot(true)
+    }
 
-            // Safety: buffer capacity is passed to byond, which makes sure it writes in-bound
-            let initial_res =
-                unsafe { byond().Byond_ReadListAssoc(&self.0, buff.as_mut_ptr().cast(), &mut len) };
-            match (initial_res, len) {
-                (false, 1..) => {
-                    buff.reserve_exact(len as usize);
-                    // Safety: buffer capacity is passed to byond, which makes sure it writes in-bound
-                    unsafe {
-                        map_byond_error!(byond().Byond_ReadListAssoc(
-                            &self.0,
-                            buff.as_mut_ptr().cast(),
-                            &mut len
-                        ))?
-                    };
-                    // Safety: buffer should be written to at this point
-                    unsafe { buff.set_len(len as usize) };
-                    Ok(std::mem::take(buff))
-                }
-                (true, _) => {
-                    // Safety: buffer should be written to at this point
-                    unsafe { buff.set_len(len as usize) };
-                    Ok(std::mem::take(buff))
-                }
-                (false, 0) => Err(Error::get_last_byond_error()),
-            }
-        })
+    /// Same as [`ByondValue::get_list_values`], but reads into `buff`, replacing what was in it
+    pub fn read_list_into(&self, buff: &mut Vec<ByondValue>) -> Result<(), Error> {
+        self.read_list_raw(buff, false)
+    }
+
+    /// Same as [`ByondValue::get_list`], but reads into `buff`, replacing what was in it
+    pub fn read_list_assoc_into(&self, buff: &mut Vec<ByondValue>) -> Result<(), Error> {
+        self.read_list_raw(buff, true)
     }
 
     /// Writes an array to the list
@@ -126,6 +152,7 @@ impl ByondValue {
         unsafe {
             map_byond_error!(byond().Byond_ReadListIndex(&self.0, &index.0, &mut result.0))?;
         }
+        super::temp::track(&result);
         Ok(result)
     }
 
@@ -160,7 +187,8 @@ impl ByondValue {
             return Ok(None);
         }
         let value = self.read_list_index(len as f32)?;
-        self.call_id(byond_string!("Remove"), &[value])?;
+        // Cut by index, Remove goes by value and can take out the wrong item with duplicates
+        self.call_id(byond_string!("Cut"), &[(len as f32).into(), 0.0.into()])?;
         Ok(Some(value))
     }
 }
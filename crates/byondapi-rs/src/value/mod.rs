@@ -3,6 +3,7 @@
 use byondapi_sys::{ByondValueType, CByondValue};
 
 use crate::static_global::byond;
+use types::ValueType;
 
 /// [Newtype](https://doc.rust-lang.org/rust-by-example/generics/new_types.html) pattern over [`CByondValue`]
 /// WARNING: If this value is a ref created by byond passed to byondapi, it's a temp ref and will be deleted in a while
@@ -18,11 +19,17 @@ pub mod builtins;
 pub mod constructors;
 pub mod conversion;
 pub mod functions;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod list;
 pub mod pointer;
 pub mod refcounted;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod temp;
 pub mod trait_impls;
 pub mod types;
+pub mod weak;
 
 /// TODO: Use a Byond_IsPtr here instead of checking the type by hand
 fn is_pointer_shim(value: &ByondValue) -> bool {
@@ -61,6 +68,11 @@ impl ByondValue {
         is_pointer_shim(self)
     }
 
+    /// Whether BYOND counts references to this, which is everything but nulls and numbers
+    pub(crate) fn is_refcounted(&self) -> bool {
+        self.0.type_ != ValueType::Null as u8 && self.0.type_ != ValueType::Number as u8
+    }
+
     pub fn is_true(&self) -> bool {
         // Safety: This operation only fails if our CByondValue is invalid, which cannot happen.
         unsafe { byond().ByondValue_IsTrue(&self.0) }
@@ -18,6 +18,7 @@ impl ByondValuePointer {
             map_byond_error!(byond().Byond_ReadPointer(&self.0 .0, &mut new_value.0))?;
         }
 
+        super::temp::track(&new_value);
         Ok(new_value)
     }
 
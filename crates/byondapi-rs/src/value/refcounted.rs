@@ -32,3 +32,59 @@ impl Drop for RcByondValue {
         self.0.decrement_ref();
     }
 }
+
+/// A persistent reference that can be cached and shared between threads, for holding on to
+/// values across ticks. Creating or cloning one increments the persistent refcount, dropping it
+/// decrements it again.
+///
+/// Off the main thread the refcount calls block until BYOND gets to them, so don't drop these on
+/// a thread the main thread is waiting on.
+#[derive(Debug)]
+pub struct PersistentRef(ByondValue);
+
+// Safety: the value itself is plain data, and BYOND synchronises the calls made through it
+unsafe impl Sync for PersistentRef {}
+
+impl PersistentRef {
+    pub fn new(mut value: ByondValue) -> Self {
+        value.increment_ref();
+        PersistentRef(value)
+    }
+
+    /// The referenced value, valid for as long as this is around
+    pub fn get(&self) -> ByondValue {
+        self.0
+    }
+
+    /// Whether the value still exists, see [`ByondValue::test_ref`]
+    pub fn is_valid(&self) -> bool {
+        let mut value = self.0;
+        value.test_ref()
+    }
+}
+
+impl From<ByondValue> for PersistentRef {
+    fn from(value: ByondValue) -> Self {
+        PersistentRef::new(value)
+    }
+}
+
+impl Clone for PersistentRef {
+    fn clone(&self) -> Self {
+        PersistentRef::new(self.0)
+    }
+}
+
+impl Deref for PersistentRef {
+    type Target = ByondValue;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for PersistentRef {
+    fn drop(&mut self) {
+        self.0.decrement_ref();
+    }
+}
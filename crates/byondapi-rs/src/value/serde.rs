@@ -0,0 +1,737 @@
+//! [serde](https://serde.rs) support, only available with the `serde` feature.
+//!
+//! Values map to the serde data model like this:
+//! - null is unit and [`None`]
+//! - numbers are numbers, and bools on the way in (anything but 0 is true)
+//! - strings are strings, chars and unit enum variants
+//! - lists with no associated values are sequences, tuples and bytes
+//! - assoc lists are maps and structs, other enum variants are a list of one `variant = content`
+//! - datums are structs too, field names are read as vars
+//!
+//! Numbers can't be assoc list keys in DM, so maps with number keys fail to serialize.
+use serde::{
+    de::{self, IntoDeserializer, Unexpected},
+    ser::{self, Serialize},
+};
+
+use super::{
+    conversion::{FromByond, ToByond},
+    ByondValue,
+};
+use crate::Error;
+
+/// Serializes `value` into a new [`ByondValue`], see the [module docs](self) for how.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<ByondValue, Error> {
+    value.serialize(Serializer)
+}
+
+/// Deserializes a `T` out of `value`, see the [module docs](self) for how.
+pub fn from_value<T: de::DeserializeOwned>(value: &ByondValue) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(*value))
+}
+
+/// Lets serde types be used as bind arguments and returns, through [`FromByond`] and [`ToByond`].
+/// ```ignore
+/// #[byondapi::bind]
+/// fn save_config(config: Serde<Config>) -> eyre::Result<()> { .. }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Serde<T>(pub T);
+
+impl<T: de::DeserializeOwned> FromByond for Serde<T> {
+    fn from_byond(value: &ByondValue) -> Result<Self, Error> {
+        from_value(value).map(Serde)
+    }
+}
+
+impl<T: Serialize> ToByond for Serde<T> {
+    fn to_byond(&self) -> Result<ByondValue, Error> {
+        to_value(&self.0)
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Serde(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Serde(msg.to_string())
+    }
+}
+
+/// Makes DM values, see [`to_value`]
+pub struct Serializer;
+
+/// Wraps `content` into a `list(variant = content)`
+fn wrap_variant(variant: &'static str, content: ByondValue) -> Result<ByondValue, Error> {
+    let mut list = ByondValue::new_list()?;
+    list.write_list_index_internal(&ByondValue::new_str(variant)?, &content)?;
+    Ok(list)
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<ByondValue, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ByondValue, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_char(self, v: char) -> Result<ByondValue, Error> {
+        ByondValue::new_str(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<ByondValue, Error> {
+        ByondValue::new_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<ByondValue, Error> {
+        v.to_byond()
+    }
+
+    fn serialize_none(self) -> Result<ByondValue, Error> {
+        Ok(ByondValue::null())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<ByondValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ByondValue, Error> {
+        Ok(ByondValue::null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ByondValue, Error> {
+        Ok(ByondValue::null())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<ByondValue, Error> {
+        ByondValue::new_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ByondValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<ByondValue, Error> {
+        wrap_variant(variant, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or_default()),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            list: ByondValue::new_list()?,
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, Error> {
+        let mut map = self.serialize_map(Some(len))?;
+        map.variant = Some(variant);
+        Ok(map)
+    }
+}
+
+/// Collects the items of a list, see [`Serializer`]
+pub struct SeqSerializer {
+    items: Vec<ByondValue>,
+    variant: Option<&'static str>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ByondValue, Error> {
+        let list = ByondValue::try_from(self.items.as_slice())?;
+        match self.variant {
+            Some(variant) => wrap_variant(variant, list),
+            None => Ok(list),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+/// Writes keys and values into an assoc list, see [`Serializer`]
+pub struct MapSerializer {
+    list: ByondValue,
+    key: Option<ByondValue>,
+    variant: Option<&'static str>,
+}
+
+impl MapSerializer {
+    fn write(&mut self, key: ByondValue, value: ByondValue) -> Result<(), Error> {
+        if key.is_num() || key.is_null() {
+            return Err(Error::Serde(format!(
+                "{key:?} can't be an assoc list key, use strings or references"
+            )));
+        }
+        self.list.write_list_index_internal(&key, &value)
+    }
+
+    fn finish(self) -> Result<ByondValue, Error> {
+        match self.variant {
+            Some(variant) => wrap_variant(variant, self.list),
+            None => Ok(self.list),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::Serde("serialize_value called before serialize_key".into()))?;
+        let value = value.serialize(Serializer)?;
+        self.write(key, value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let value = value.serialize(Serializer)?;
+        self.write(ByondValue::new_str(key)?, value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = ByondValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<ByondValue, Error> {
+        self.finish()
+    }
+}
+
+/// Reads DM values, see [`from_value`]
+#[derive(Clone, Copy)]
+pub struct Deserializer {
+    value: ByondValue,
+}
+
+impl Deserializer {
+    pub fn new(value: ByondValue) -> Self {
+        Self { value }
+    }
+
+    fn unexpected(&self) -> Unexpected<'static> {
+        let value = &self.value;
+        if value.is_null() {
+            Unexpected::Unit
+        } else if let Ok(num) = value.get_number() {
+            Unexpected::Float(num.into())
+        } else if value.is_str() {
+            Unexpected::Other("string")
+        } else if value.is_list() {
+            Unexpected::Other("list")
+        } else {
+            Unexpected::Other("reference")
+        }
+    }
+
+    /// The list as a flat `key, value, key, value..` vec
+    fn pairs(&self) -> Result<Vec<ByondValue>, Error> {
+        self.value.get_list()
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value;
+        if value.is_null() {
+            visitor.visit_unit()
+        } else if value.is_num() {
+            let num = value.get_number()?;
+            if num.fract() != 0.0 || num.abs() >= i64::MAX as f32 {
+                visitor.visit_f32(num)
+            } else if num >= 0.0 {
+                visitor.visit_u64(num as u64)
+            } else {
+                visitor.visit_i64(num as i64)
+            }
+        } else if value.is_str() {
+            visitor.visit_string(value.get_string()?)
+        } else if value.is_list() {
+            let pairs = self.pairs()?;
+            if pairs.chunks_exact(2).any(|pair| !pair[1].is_null()) {
+                visitor.visit_map(MapAccess::new(pairs))
+            } else {
+                visitor.visit_seq(SeqAccess::new(pairs.into_iter().step_by(2).collect()))
+            }
+        } else {
+            Err(de::Error::invalid_type(self.unexpected(), &visitor))
+        }
+    }
+
+    fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_null() {
+            visitor.visit_bool(false)
+        } else if self.value.is_num() {
+            visitor.visit_bool(self.value.get_number()? != 0.0)
+        } else {
+            Err(de::Error::invalid_type(self.unexpected(), &visitor))
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_null() {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::invalid_type(self.unexpected(), &visitor))
+        }
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if !self.value.is_list() {
+            return Err(de::Error::invalid_type(self.unexpected(), &visitor));
+        }
+        visitor.visit_seq(SeqAccess::new(self.value.get_list_values()?))
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if !self.value.is_list() {
+            return Err(de::Error::invalid_type(self.unexpected(), &visitor));
+        }
+        visitor.visit_map(MapAccess::new(self.pairs()?))
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value = self.value;
+        if value.is_list() {
+            self.deserialize_map(visitor)
+        } else if value.is_null() || value.is_num() || value.is_str() || value.is_ptr() {
+            Err(de::Error::invalid_type(self.unexpected(), &visitor))
+        } else {
+            visitor.visit_map(VarAccess {
+                datum: value,
+                fields: fields.iter(),
+                value: None,
+            })
+        }
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.value.is_str() {
+            return visitor.visit_enum(self.value.get_string()?.into_deserializer());
+        }
+        if !self.value.is_list() {
+            return Err(de::Error::invalid_type(self.unexpected(), &visitor));
+        }
+        match self.pairs()?.as_slice() {
+            [variant, content] => visitor.visit_enum(EnumAccess {
+                variant: *variant,
+                content: *content,
+            }),
+            _ => Err(de::Error::invalid_value(
+                Unexpected::Other("list"),
+                &"a list with exactly one variant = content pair",
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string identifier
+    }
+}
+
+struct SeqAccess {
+    items: std::vec::IntoIter<ByondValue>,
+}
+
+impl SeqAccess {
+    fn new(items: Vec<ByondValue>) -> Self {
+        Self {
+            items: items.into_iter(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.items
+            .next()
+            .map(|item| seed.deserialize(Deserializer::new(item)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess {
+    pairs: std::vec::IntoIter<ByondValue>,
+    value: Option<ByondValue>,
+}
+
+impl MapAccess {
+    fn new(pairs: Vec<ByondValue>) -> Self {
+        Self {
+            pairs: pairs.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some(key) = self.pairs.next() else {
+            return Ok(None);
+        };
+        self.value = self.pairs.next();
+        seed.deserialize(Deserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.value.take().unwrap_or_default();
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len() / 2)
+    }
+}
+
+/// Reads struct fields off a datum's vars, vars that don't exist are left out
+struct VarAccess {
+    datum: ByondValue,
+    fields: std::slice::Iter<'static, &'static str>,
+    value: Option<ByondValue>,
+}
+
+impl<'de> de::MapAccess<'de> for VarAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        for field in self.fields.by_ref() {
+            if let Ok(value) = self.datum.read_var(*field) {
+                self.value = Some(value);
+                return seed.deserialize((*field).into_deserializer()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.value.take().unwrap_or_default();
+        seed.deserialize(Deserializer::new(value))
+    }
+}
+
+struct EnumAccess {
+    variant: ByondValue,
+    content: ByondValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer), Error> {
+        let variant = seed.deserialize(Deserializer::new(self.variant))?;
+        Ok((variant, Deserializer::new(self.content)))
+    }
+}
+
+/// The content of a `list(variant = content)` enum
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
@@ -0,0 +1,125 @@
+//! Letting go of temporary references before the end of the tick.
+//!
+//! Every value the api hands out on the main thread holds a temporary reference until the tick
+//! ends, so a loop over a big list can pile up a lot of them. A [`TempScope`] records the values
+//! made while it's alive and releases them with [`ByondValue::decrement_tempref`] when it's
+//! dropped:
+//! ```ignore
+//! for mob in mobs.iter() {
+//!     let scope = TempScope::new();
+//!     let name = mob.read_var("name")?;
+//!     let loc = mob.read_var("loc")?;
+//!     scope.keep(&loc);
+//!     turfs.push(loc);
+//! } // `name` is released here, `loc` lasts until the tick ends like usual
+//! ```
+//! Only use these on the main thread outside of [`thread_sync`](crate::threadsync::thread_sync),
+//! everywhere else the api makes persistent references that need
+//! [`ByondValue::decrement_ref`] instead.
+//!
+//! Without [`Feature::DecTempRef`] scopes record and release nothing. 515 can only let go of a
+//! temporary reference with `ByondValue_DecRef`, which takes a persistent one first if there is
+//! one, like the one a [`PersistentRef`](super::refcounted::PersistentRef) made in the scope holds.
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+};
+
+use super::ByondValue;
+use crate::{static_global::byond, Feature};
+
+thread_local! {
+    /// Values made while any scope is alive, each scope owns everything past where it started
+    static RECORDED: RefCell<Vec<ByondValue>> = const { RefCell::new(Vec::new()) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether there's a scope to record values with, and a way to release them safely
+fn recording() -> bool {
+    DEPTH.get() != 0 && byond().supports(Feature::DecTempRef)
+}
+
+/// Records `value` with the innermost [`TempScope`], if there is one
+pub(crate) fn track(value: &ByondValue) {
+    if !recording() || !value.is_refcounted() {
+        return;
+    }
+    RECORDED.with_borrow_mut(|recorded| recorded.push(*value));
+}
+
+/// Same as [`track`] for every value in `values`
+pub(crate) fn track_all(values: &[ByondValue]) {
+    if !recording() {
+        return;
+    }
+    RECORDED.with_borrow_mut(|recorded| {
+        recorded.extend(values.iter().filter(|value| value.is_refcounted()))
+    });
+}
+
+fn same(a: &ByondValue, b: &ByondValue) -> bool {
+    // Safety: every value we record is refcounted, so the data is a ref
+    a.0.type_ == b.0.type_ && unsafe { a.0.data.ref_ == b.0.data.ref_ }
+}
+
+/// Guard that releases the temporary references of every value made while it's alive, except
+/// for the ones passed to [`TempScope::keep`]. Scopes can be nested, the innermost one gets the
+/// values. See the [module docs](self).
+pub struct TempScope {
+    start: usize,
+    // The recorded values are per thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl TempScope {
+    pub fn new() -> Self {
+        DEPTH.set(DEPTH.get() + 1);
+        TempScope {
+            start: RECORDED.with_borrow(Vec::len),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Runs `f` in a new scope. Values `f` makes are released once it returns, including the one
+    /// it returns unless it [`keep`](Self::keep)s that
+    pub fn run<R>(f: impl FnOnce(&TempScope) -> R) -> R {
+        f(&TempScope::new())
+    }
+
+    /// Leaves one temporary reference to `value` alone, so it lasts until the end of the tick
+    /// like it would without a scope
+    pub fn keep(&self, value: &ByondValue) {
+        RECORDED.with_borrow_mut(|recorded| {
+            let start = self.start.min(recorded.len());
+            if let Some(index) = recorded[start..].iter().rposition(|v| same(v, value)) {
+                recorded.remove(start + index);
+            }
+        })
+    }
+
+    /// How many temporary references are waiting to be released by this scope
+    pub fn len(&self) -> usize {
+        RECORDED.with_borrow(|recorded| recorded.len().saturating_sub(self.start))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for TempScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempScope {
+    fn drop(&mut self) {
+        let released =
+            RECORDED.with_borrow_mut(|recorded| recorded.split_off(self.start.min(recorded.len())));
+        DEPTH.set(DEPTH.get() - 1);
+        for mut value in released {
+            value.decrement_tempref();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Weak references to datums that notice when the datum is gone.
+use std::{
+    ffi::CString,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::ByondValue;
+use crate::{byond_string, error::ResultExt, object::Datum, Error};
+
+/// Start of the values [`WeakByondValue::new`] stamps datums with
+const STAMP_PREFIX: &str = "byondapi_weak_";
+
+/// A value no other datum holds in its stamp var, even ones stamped before the library was reloaded
+fn next_stamp() -> String {
+    static LOADED_AT: OnceLock<u128> = OnceLock::new();
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let loaded_at = LOADED_AT.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos())
+    });
+    format!(
+        "{STAMP_PREFIX}{loaded_at:x}_{}",
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// What a var held when the [`WeakByondValue`] was made
+#[derive(Debug, Clone, PartialEq)]
+enum Marker {
+    Null,
+    Num(f32),
+    Str(String),
+    Other,
+}
+
+impl Marker {
+    fn of(value: ByondValue) -> Self {
+        if value.is_null() {
+            Marker::Null
+        } else if let Ok(num) = value.get_number() {
+            Marker::Num(num)
+        } else if let Ok(string) = value.get_string() {
+            Marker::Str(string)
+        } else {
+            Marker::Other
+        }
+    }
+}
+
+/// A reference to a datum that doesn't keep it alive, and that [`upgrade`](Self::upgrade)s to
+/// nothing once the datum was deleted.
+///
+/// BYOND reuses the ref ids of deleted datums, so on top of [`ByondValue::test_ref`] the datum's
+/// type path and a var holding something unique to it are remembered and compared. Either
+/// [`WeakByondValue::new`] stamps the var, or [`WeakByondValue::with_marker`] uses one the datum
+/// already has.
+#[derive(Debug, Clone)]
+pub struct WeakByondValue {
+    value: ByondValue,
+    type_path: String,
+    marker_var: CString,
+    marker: Marker,
+}
+
+impl WeakByondValue {
+    /// Makes a weak reference, stamping the datum's var `stamp_var` with a value no other datum
+    /// has unless an earlier weak reference already did. The type has to define the var and leave
+    /// it null for this:
+    /// ```dm
+    /// /datum/var/weak_stamp
+    /// ```
+    ///
+    /// # Errors
+    /// [`Error::UnexpectedType`] if `value` isn't a datum, [`Error::InvalidConversion`] if the var
+    /// holds something that isn't a stamp, or whatever reading or writing the var fails with
+    pub fn new<T: Into<Vec<u8>>>(mut value: ByondValue, stamp_var: T) -> Result<Self, Error> {
+        let datum = Datum::try_from(value)?;
+        let stamp_var = CString::new(stamp_var).map_err(|_| Error::InvalidConversion)?;
+        let current = value.read_var(stamp_var.as_bytes())?;
+        let stamp = match current.get_string() {
+            Ok(stamp) if stamp.starts_with(STAMP_PREFIX) => stamp,
+            _ if current.is_null() => {
+                let stamp = next_stamp();
+                value.write_var(stamp_var.as_bytes(), &ByondValue::new_str(stamp.as_str())?)?;
+                stamp
+            }
+            _ => {
+                return Err(Error::InvalidConversion).with_context(|| {
+                    format!(
+                        "stamping var '{}' for a weak reference, it holds something else",
+                        stamp_var.to_string_lossy()
+                    )
+                })
+            }
+        };
+        Ok(WeakByondValue {
+            value,
+            type_path: datum.type_path()?,
+            marker_var: stamp_var,
+            marker: Marker::Str(stamp),
+        })
+    }
+
+    /// Makes a weak reference checked against the datum's var `marker_var`, which has to hold
+    /// something no other datum of the type ever holds, like an id given out on `New()`. With a
+    /// var that's null or shared, a new datum reusing the ref looks like the old one.
+    ///
+    /// # Errors
+    /// [`Error::UnexpectedType`] if `value` isn't a datum, or whatever reading the var fails with
+    pub fn with_marker<T: Into<Vec<u8>>>(value: ByondValue, marker_var: T) -> Result<Self, Error> {
+        let datum = Datum::try_from(value)?;
+        let marker_var = CString::new(marker_var).map_err(|_| Error::InvalidConversion)?;
+        Ok(WeakByondValue {
+            value,
+            type_path: datum.type_path()?,
+            marker: Marker::of(value.read_var(marker_var.as_bytes())?),
+            marker_var,
+        })
+    }
+
+    /// The datum if it still exists and is still the one this was made from
+    pub fn upgrade(&self) -> Option<ByondValue> {
+        let mut value = self.value;
+        if !value.test_ref() {
+            return None;
+        }
+        let type_path = value.read_var_id(byond_string!("type")).ok()?;
+        if type_path.get_string().ok()? != self.type_path {
+            return None;
+        }
+        let marker = value.read_var(self.marker_var.as_bytes()).ok()?;
+        (Marker::of(marker) == self.marker).then_some(value)
+    }
+
+    /// Whether [`upgrade`](Self::upgrade) would fail
+    pub fn is_stale(&self) -> bool {
+        self.upgrade().is_none()
+    }
+}
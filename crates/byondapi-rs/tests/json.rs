@@ -0,0 +1,77 @@
+//! `cargo test --package byondapi --features mock,json`
+#![cfg(all(feature = "mock", feature = "json"))]
+
+use byondapi::{
+    mock,
+    prelude::*,
+    value::json::{JsonOptions, RefHandling},
+    Error,
+};
+use serde_json::json;
+
+#[test]
+fn round_trip() {
+    let value = json!({
+        "name": "station",
+        "ratio": 0.1,
+        "players": [1, 2, 3],
+        "nested": {"empty": [], "nothing": null},
+        "enabled": 1
+    });
+    let list = ByondValue::from_json(&value).unwrap();
+    assert_eq!(
+        list.read_list_index("ratio").unwrap().get_number().unwrap(),
+        0.1
+    );
+    assert_eq!(list.to_json().unwrap(), value);
+}
+
+#[test]
+fn refs() {
+    mock::register_type("/datum/holder", &[("held", ByondValue::null())]);
+    let mut datum =
+        ByondValue::builtin_new(ByondValue::new_str("/datum/holder").unwrap(), &[]).unwrap();
+    datum.write_var("held", &5.0.into()).unwrap();
+    let list = ByondValue::try_from([datum].as_slice()).unwrap();
+
+    let ref_string = list.to_json().unwrap()[0].as_str().unwrap().to_owned();
+    assert!(ref_string.starts_with("[0x21"));
+
+    let vars = list
+        .to_json_with(&JsonOptions {
+            refs: RefHandling::Vars,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(vars[0]["held"], 5);
+    assert!(vars[0].get("vars").is_none());
+
+    let error = list.to_json_with(&JsonOptions {
+        refs: RefHandling::Error,
+        ..Default::default()
+    });
+    assert!(matches!(error, Err(Error::JsonRefNotAllowed(_))));
+}
+
+#[test]
+fn cycles_and_depth() {
+    let mut outer = ByondValue::new_list().unwrap();
+    let inner = ByondValue::try_from([outer].as_slice()).unwrap();
+    outer.push_list(inner).unwrap();
+    assert!(matches!(outer.to_json(), Err(Error::JsonCycle(_))));
+
+    // The same list twice is fine as long as it doesn't contain itself
+    let leaf = ByondValue::try_from([1.0.into()].as_slice()).unwrap();
+    let twice = ByondValue::try_from([leaf, leaf].as_slice()).unwrap();
+    assert_eq!(twice.to_json().unwrap(), serde_json::json!([[1], [1]]));
+
+    let deep = ByondValue::from_json(&serde_json::json!([[[[]]]])).unwrap();
+    let options = JsonOptions {
+        max_depth: 3,
+        ..Default::default()
+    };
+    assert!(matches!(
+        deep.to_json_with(&options),
+        Err(Error::JsonTooDeep(3))
+    ));
+}
@@ -0,0 +1,1022 @@
+//! Runs the api against the in-process fake, no BYOND install needed:
+//! `cargo test --package byondapi --features mock`
+#![cfg(feature = "mock")]
+
+use std::collections::HashMap;
+
+use byondapi::{
+    byond, byond_string, exception::Exception, map::*, mock, object::*, pixloc::byond_pixloc,
+    prelude::*, Error, Feature,
+};
+
+fn new_obj(path: &str) -> ByondValue {
+    ByondValue::builtin_new(ByondValue::new_str(path).unwrap(), &[]).unwrap()
+}
+
+#[byondapi::bind]
+fn mock_get_name(object: ByondValue) -> Result<ByondValue, Error> {
+    object.call("get_name", &[])
+}
+
+#[byondapi::bind]
+fn mock_list_sum(list: ByondValue) -> Result<ByondValue, Error> {
+    let mut sum = 0.;
+    for item in list.get_list_values()? {
+        sum += item.get_number()?;
+    }
+    Ok(sum.into())
+}
+
+#[byondapi::bind]
+fn mock_repeat(text: String, times: Option<u8>) -> Result<String, Error> {
+    Ok(text.repeat(times.unwrap_or(1) as usize))
+}
+
+#[byondapi::bind]
+fn mock_split(text: String) -> Result<HashMap<String, (usize, bool)>, Error> {
+    Ok(text
+        .split(' ')
+        .map(|word| (word.to_owned(), (word.len(), word.is_empty())))
+        .collect())
+}
+
+#[derive(FromByond, ToByond, Debug, PartialEq)]
+#[byond(path = "/datum/reagent")]
+struct Reagent {
+    name: String,
+    #[byond(rename = "volume")]
+    amount: f32,
+    #[byond(default)]
+    color: Option<String>,
+    #[byond(skip)]
+    cached: u32,
+}
+
+#[derive(FromByond, ToByond, Debug, PartialEq)]
+#[byond(assoc_list)]
+struct Settings {
+    volume: u8,
+    #[byond(rename = "ckey")]
+    owner: String,
+    #[byond(default)]
+    muted: bool,
+}
+
+#[byondapi::bind]
+fn mock_panic(index: usize) -> Result<ByondValue, Error> {
+    Ok([ByondValue::null()][index])
+}
+
+#[byondapi::bind_raw_args]
+fn mock_panic_raw() -> Result<ByondValue, Error> {
+    panic!("{} args", args.len())
+}
+
+#[byondapi::bind("/proc/mock_no_catch", no_catch_unwind)]
+fn mock_no_catch() -> Result<ByondValue, Error> {
+    panic!("meow")
+}
+
+#[byondapi::bind(async)]
+async fn mock_async(text: String, times: u8) -> Result<String, Error> {
+    if times == 0 {
+        return Err(Error::InvalidConversion);
+    }
+    Ok(text.repeat(times as usize))
+}
+
+#[byondapi::bind(async)]
+async fn mock_async_runtime() -> Result<(), Error> {
+    // What a BYOND call that runtimes does under the mock
+    std::panic::panic_any(mock::Runtime("out of cheese".to_owned()))
+}
+
+#[byondapi::bind]
+fn mock_mob_name(mob: Mob) -> Result<String, Error> {
+    mob.name()
+}
+
+#[byondapi::bind]
+fn mock_throw(wrap: bool) -> Result<ByondValue, Error> {
+    let exception = Exception::new("Out of cheese")
+        .of_type("/exception/cheese")
+        .desc("Redo from start")
+        .var("wedges", ByondValue::new_num(0.));
+    if wrap {
+        Err(Error::from(exception).context("making a sandwich"))
+    } else {
+        Err(exception.into())
+    }
+}
+
+#[byondapi::bind]
+fn mock_throw_eyre() -> eyre::Result<ByondValue> {
+    Err(eyre::Report::new(Exception::new("Out of cheese")).wrap_err("making a sandwich"))
+}
+
+#[byondapi::bind]
+fn mock_throw_boxed() -> Result<ByondValue, Box<dyn std::error::Error + Send + Sync>> {
+    Err(Error::from(Exception::new("Out of cheese"))
+        .context("making a sandwich")
+        .into())
+}
+
+#[byondapi::bind]
+fn mock_throw_str() -> Result<ByondValue, &'static str> {
+    Err("Out of cheese")
+}
+
+#[byondapi::bind_macro]
+fn mock_throw_macro() -> Result<ByondValue, Exception> {
+    Err(Exception::new("Out of cheese"))
+}
+
+#[test]
+fn read_write_var() {
+    mock::register_type("/datum/data", &[("test_name", "dust".try_into().unwrap())]);
+    let mut data = new_obj("/datum/data");
+
+    assert_eq!(data.read_string("test_name").unwrap(), "dust");
+    assert_eq!(
+        data.read_var_id(byond_string!("test_name"))
+            .unwrap()
+            .get_string()
+            .unwrap(),
+        "dust"
+    );
+
+    data.write_var("test_name", &ByondValue::new_num(5.0))
+        .unwrap();
+    assert_eq!(data.read_number("test_name").unwrap(), 5.0);
+
+    assert!(data.read_var("not_a_var").is_err());
+}
+
+#[test]
+fn proc_call() {
+    mock::register_type("/obj/thing", &[]);
+    mock::register_proc("/obj", "get_name", |src, _| src.read_var("name"));
+    mock::register_global_proc("add", |args| {
+        Ok((args[0].get_number()? + args[1].get_number()?).into())
+    });
+
+    let thing = new_obj("/obj/thing");
+    assert_eq!(
+        thing.call("get_name", &[]).unwrap().get_string().unwrap(),
+        "thing"
+    );
+    assert!(thing.call("not_a_proc_anywhere", &[]).is_err());
+
+    let sum = byondapi::global_call::call_global("add", &[1.0.into(), 2.0.into()]).unwrap();
+    assert_eq!(sum.get_number().unwrap(), 3.0);
+}
+
+#[test]
+fn lists() {
+    let items = [1.0, 2.0, 3.0].map(ByondValue::from);
+    let mut list = ByondValue::try_from(items.as_slice()).unwrap();
+
+    assert_eq!(list.get_list_values().unwrap(), items);
+    assert_eq!(list.builtin_length().unwrap().get_number().unwrap(), 3.0);
+    assert_eq!(
+        list.read_list_index(2.0).unwrap().get_number().unwrap(),
+        2.0
+    );
+
+    list.push_list(4.0.into()).unwrap();
+    assert_eq!(list.pop_list().unwrap(), Some(4.0.into()));
+    assert_eq!(list.get_list_values().unwrap().len(), 3);
+
+    let mut assoc = ByondValue::new_list().unwrap();
+    assoc.write_list_index("cat", 7.0).unwrap();
+    assoc.write_list_index("dog", 5.0).unwrap();
+    assert_eq!(
+        assoc.read_list_index("dog").unwrap().get_number().unwrap(),
+        5.0
+    );
+    assert_eq!(
+        assoc.get_list().unwrap(),
+        [
+            "cat".try_into().unwrap(),
+            7.0.into(),
+            "dog".try_into().unwrap(),
+            5.0.into()
+        ]
+    );
+}
+
+#[test]
+fn block() {
+    mock::set_map_size(2, 2, 1);
+
+    let block = byond_block(
+        ByondXYZ::with_coords((1, 1, 1)),
+        ByondXYZ::with_coords((2, 2, 1)),
+    )
+    .unwrap();
+    assert_eq!(block.len(), 4);
+
+    let turf = byond_locatexyz(ByondXYZ::with_coords((2, 1, 1))).unwrap();
+    assert_eq!(block[1], turf);
+    assert_eq!(byond_xyz(&turf).unwrap().coordinates(), (2, 1, 1));
+
+    assert!(byond_locatexyz(ByondXYZ::with_coords((3, 1, 1)))
+        .unwrap()
+        .is_null());
+}
+
+#[test]
+fn pointers() {
+    let pointer = mock::new_pointer("meow".try_into().unwrap());
+    let value = pointer.read().unwrap();
+    pointer
+        .write(
+            &format!("awa{}", value.get_string().unwrap())
+                .try_into()
+                .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(pointer.read().unwrap().get_string().unwrap(), "awameow");
+}
+
+#[test]
+fn binds() {
+    mock::register_type("/obj/thing", &[]);
+    mock::register_proc("/obj", "get_name", |src, _| src.read_var("name"));
+
+    let name = mock::call_ffi(mock_get_name_ffi, &[new_obj("/obj/thing")]).unwrap();
+    assert_eq!(name.get_string().unwrap(), "thing");
+
+    let list = ByondValue::try_from([1.0, 2.0, 3.0].map(ByondValue::from).as_slice()).unwrap();
+    let sum = mock::call_ffi(mock_list_sum_ffi, &[list]).unwrap();
+    assert_eq!(sum.get_number().unwrap(), 6.0);
+
+    // 515 reports errors through {libname}_stack_trace instead of a runtime
+    #[cfg(feature = "byond-516-1651")]
+    {
+        let list = ByondValue::try_from(["meow".try_into().unwrap()].as_slice()).unwrap();
+        assert!(mock::call_ffi(mock_list_sum_ffi, &[list]).is_err());
+    }
+}
+
+#[test]
+fn typed_args() {
+    let meow = ByondValue::try_from("meow").unwrap();
+    let result = mock::call_ffi(mock_repeat_ffi, &[meow, 2.0.into()]).unwrap();
+    assert_eq!(result.get_string().unwrap(), "meowmeow");
+
+    // Missing arguments are null
+    let result = mock::call_ffi(mock_repeat_ffi, &[meow]).unwrap();
+    assert_eq!(result.get_string().unwrap(), "meow");
+
+    #[cfg(feature = "byond-516-1651")]
+    {
+        let error = mock::call_ffi(mock_repeat_ffi, &[meow, (-1.0).into()]).unwrap_err();
+        assert!(error.contains("Bad argument #2 (times), expected Option<u8>"));
+        assert!(mock::call_ffi(mock_repeat_ffi, &[5.0.into()]).is_err());
+    }
+
+    let values: Vec<u32> = byondapi::binds::convert_arg(
+        &[ByondValue::try_from([1.0, 2.5].map(ByondValue::from).as_slice()).unwrap()],
+        0,
+        "list",
+        "Vec<u32>",
+    )
+    .unwrap();
+    assert_eq!(values, [1, 2]);
+
+    // Numbers just past the end of a type don't saturate
+    let num = |num: f32| ByondValue::new_num(num);
+    assert_eq!(i32::from_byond(&num(-2147483648.)).unwrap(), i32::MIN);
+    assert!(i32::from_byond(&num(2147483648.)).is_err());
+    assert_eq!(i32::from_byond(&num(2147483520.)).unwrap(), 2147483520);
+    assert!(u32::from_byond(&num(4294967296.)).is_err());
+    assert_eq!(u32::from_byond(&num(4294967040.)).unwrap(), 4294967040);
+    assert!(u32::from_byond(&num(-1.)).is_err());
+    assert!(i64::from_byond(&num(9223372036854775808.)).is_err());
+    assert_eq!(
+        i64::from_byond(&num(-9223372036854775808.)).unwrap(),
+        i64::MIN
+    );
+    assert!(u64::from_byond(&num(18446744073709551616.)).is_err());
+    assert_eq!(u8::from_byond(&num(255.9)).unwrap(), 255);
+    assert!(u8::from_byond(&num(256.)).is_err());
+}
+
+#[test]
+fn return_conversion() {
+    let text = ByondValue::try_from("cat meow").unwrap();
+    let words = mock::call_ffi(mock_split_ffi, &[text]).unwrap();
+    let words = HashMap::<String, Vec<f32>>::from_byond(&words).unwrap();
+    assert_eq!(words["cat"], [3.0, 0.0]);
+    assert_eq!(words["meow"], [4.0, 0.0]);
+
+    assert!(().to_byond().unwrap().is_null());
+    assert!(None::<u8>.to_byond().unwrap().is_null());
+    let nested = vec![vec![1, 2], vec![3]].to_byond().unwrap();
+    assert_eq!(
+        Vec::<Vec<u8>>::from_byond(&nested).unwrap(),
+        [vec![1, 2], vec![3]]
+    );
+}
+
+#[test]
+fn derive() {
+    mock::register_type(
+        "/datum/reagent",
+        &[
+            ("name", "water".try_into().unwrap()),
+            ("volume", 10.0.into()),
+            ("color", ByondValue::null()),
+        ],
+    );
+    let mut datum = new_obj("/datum/reagent");
+    let mut reagent = Reagent::from_byond(&datum).unwrap();
+    assert_eq!(
+        reagent,
+        Reagent {
+            name: "water".to_owned(),
+            amount: 10.0,
+            color: None,
+            cached: 0,
+        }
+    );
+
+    reagent.amount = 5.0;
+    reagent.color = Some("#0000ff".to_owned());
+    reagent.write_vars(&mut datum).unwrap();
+    assert_eq!(datum.read_number("volume").unwrap(), 5.0);
+    assert_eq!(datum.read_string("color").unwrap(), "#0000ff");
+
+    let created = reagent.to_byond().unwrap();
+    assert_eq!(Reagent::from_byond(&created).unwrap(), reagent);
+
+    let settings = Settings {
+        volume: 50,
+        owner: "dust".to_owned(),
+        muted: true,
+    };
+    let mut list = settings.to_byond().unwrap();
+    assert_eq!(
+        list.read_list_index("ckey").unwrap().get_string().unwrap(),
+        "dust"
+    );
+    assert_eq!(Settings::from_byond(&list).unwrap(), settings);
+
+    list.write_list_index("muted", ByondValue::null()).unwrap();
+    assert!(!Settings::from_byond(&list).unwrap().muted);
+    assert!(Settings::from_byond(&5.0.into()).is_err());
+}
+
+#[test]
+fn byond_list() {
+    let mut list: ByondList = [1.0, 2.0, 3.0].map(ByondValue::from).into_iter().collect();
+    assert_eq!(list.len().unwrap(), 3);
+    assert_eq!(list.get(0).unwrap(), Some(1.0.into()));
+    assert_eq!(list.get(3).unwrap(), None);
+    assert!(list.set(3, 4.0.into()).is_err());
+
+    // Lists are pushed as a single item instead of being added item by item
+    let inner = ByondValue::try_from([5.0.into(), 6.0.into()].as_slice()).unwrap();
+    list.push(inner).unwrap();
+    assert_eq!(list.len().unwrap(), 4);
+    assert_eq!(list.pop().unwrap(), Some(inner));
+
+    list.insert(0, 0.0.into()).unwrap();
+    list.swap(0, 3).unwrap();
+    assert_eq!(
+        list.to_vec().unwrap(),
+        [3.0, 1.0, 2.0, 0.0].map(ByondValue::from)
+    );
+    assert_eq!(list.remove_at(1).unwrap(), 1.0.into());
+    assert_eq!(list.find(&0.0.into()).unwrap(), Some(2));
+    assert!(!list.contains(&1.0.into()).unwrap());
+
+    let removed = list.splice(1..2, [7.0, 8.0].map(ByondValue::from)).unwrap();
+    assert_eq!(removed, [2.0.into()]);
+    #[allow(clippy::reversed_empty_ranges)]
+    let backwards = list.splice(3..1, []).unwrap_err();
+    assert!(matches!(
+        backwards,
+        Error::InvalidRange { start: 3, end: 1 }
+    ));
+    let past_end = list.splice(2..9, []).unwrap_err();
+    assert!(matches!(
+        past_end,
+        Error::IndexOutOfBounds { index: 9, len: 4 }
+    ));
+    list.extend([9.0.into()]).unwrap();
+    assert_eq!(
+        list.into_iter().collect::<Vec<_>>(),
+        [3.0, 7.0, 8.0, 0.0, 9.0].map(ByondValue::from)
+    );
+
+    list.truncate(2).unwrap();
+    assert_eq!(list.len().unwrap(), 2);
+    list.clear().unwrap();
+    assert!(list.is_empty().unwrap());
+    assert_eq!(list.pop().unwrap(), None);
+
+    assert!(ByondList::try_from(ByondValue::new_num(1.0)).is_err());
+}
+
+#[test]
+fn pop_duplicates() {
+    let mut list = ByondValue::try_from([1.0, 2.0, 1.0].map(ByondValue::from).as_slice()).unwrap();
+    assert_eq!(list.pop_list().unwrap(), Some(1.0.into()));
+    assert_eq!(
+        list.get_list_values().unwrap(),
+        [1.0, 2.0].map(ByondValue::from)
+    );
+}
+
+#[test]
+fn byond_assoc_list() {
+    let mut list = ByondAssocList::new().unwrap();
+    assert_eq!(list.insert("cat", 7.0.into()).unwrap(), None);
+    assert_eq!(list.insert("dog", ByondValue::null()).unwrap(), None);
+    assert_eq!(list.insert("cat", 8.0.into()).unwrap(), Some(7.0.into()));
+    assert_eq!(list.len().unwrap(), 2);
+
+    assert_eq!(list.get("cat").unwrap(), Some(8.0.into()));
+    // A key with a null value is still there
+    assert_eq!(list.get("dog").unwrap(), Some(ByondValue::null()));
+    assert_eq!(list.get("cow").unwrap(), None);
+    assert!(list.contains_key("dog").unwrap());
+    assert!(list.get(1.0).is_err());
+
+    assert_eq!(
+        list.keys().unwrap(),
+        ["cat", "dog"].map(|key| ByondValue::try_from(key).unwrap())
+    );
+    assert_eq!(list.values().unwrap(), [8.0.into(), ByondValue::null()]);
+
+    assert_eq!(list.remove("dog").unwrap(), Some(ByondValue::null()));
+    assert_eq!(list.remove("dog").unwrap(), None);
+    assert_eq!(list.entries().unwrap().len(), 1);
+
+    let map = list.to_map::<HashMap<_, _>>().unwrap();
+    assert_eq!(map["cat"], 8.0.into());
+    let copy = ByondAssocList::try_from(&map).unwrap();
+    assert_eq!(
+        copy.to_map::<std::collections::BTreeMap<_, _>>().unwrap(),
+        std::collections::BTreeMap::from([("cat".to_owned(), 8.0.into())])
+    );
+
+    list.clear().unwrap();
+    assert!(list.is_empty().unwrap());
+}
+
+#[test]
+fn list_snapshots() {
+    let items = [1.0, 2.0, 3.0].map(ByondValue::from);
+    let list = ByondValue::try_from(items.as_slice()).unwrap();
+    let mut assoc = ByondValue::new_list().unwrap();
+    assoc.write_list_index("cat", 7.0).unwrap();
+
+    {
+        let snapshot = list.list_snapshot().unwrap();
+        assert_eq!(&*snapshot, items);
+        // Nested snapshots get their own buffer
+        let nested = assoc.list_assoc_snapshot().unwrap();
+        assert_eq!(nested[1], 7.0.into());
+        assert_eq!(snapshot.len(), 3);
+    }
+
+    let buffer_address = list.list_snapshot().unwrap().as_ptr();
+    assert_eq!(list.list_snapshot().unwrap().as_ptr(), buffer_address);
+
+    let mut buffer = vec![ByondValue::null(); 10];
+    list.read_list_into(&mut buffer).unwrap();
+    assert_eq!(buffer, items);
+    assoc.read_list_assoc_into(&mut buffer).unwrap();
+    assert_eq!(buffer.len(), 2);
+    assert!(ByondValue::null().read_list_into(&mut buffer).is_err());
+}
+
+#[test]
+fn handles() {
+    mock::set_map_size(3, 3, 1);
+    mock::register_type("/mob/player", &[]);
+    mock::register_type("/obj/item", &[]);
+
+    let mut mob = Mob::try_from(new_obj("/mob/player")).unwrap();
+    let turf = Turf::at(ByondXYZ::with_coords((2, 3, 1))).unwrap().unwrap();
+    mob.write_var("loc", &turf).unwrap();
+
+    assert_eq!(mob.name().unwrap(), "player");
+    assert_eq!(mob.type_path().unwrap(), "/mob/player");
+    assert_eq!(mob.loc().unwrap().unwrap().as_value(), turf.as_value());
+    assert_eq!(mob.xyz().unwrap().coordinates(), (2, 3, 1));
+    assert_eq!(mob.turf().unwrap(), Some(turf));
+    assert_eq!(mob.client().unwrap(), None);
+    assert_eq!(mob.ckey().unwrap(), None);
+    assert!(turf.contents().unwrap().contains(&mob).unwrap());
+    assert_eq!(Turf::at(ByondXYZ::with_coords((4, 1, 1))).unwrap(), None);
+    assert_eq!(World::get().max_xyz().unwrap().coordinates(), (3, 3, 1));
+
+    let item = new_obj("/obj/item");
+    assert!(Atom::try_from(item).is_ok());
+    assert!(Datum::try_from(item).is_ok());
+    assert!(matches!(
+        Mob::try_from(item),
+        Err(Error::UnexpectedType {
+            expected: "mob",
+            ..
+        })
+    ));
+    assert!(Datum::try_from(ByondValue::new_list().unwrap()).is_err());
+
+    let name = mock::call_ffi(mock_mob_name_ffi, &[mob.into_value()]).unwrap();
+    assert_eq!(name.get_string().unwrap(), "player");
+    #[cfg(feature = "byond-516-1651")]
+    {
+        let error = mock::call_ffi(mock_mob_name_ffi, &[item]).unwrap_err();
+        assert!(error.contains("Bad argument #1 (mob), expected Mob"));
+    }
+}
+
+#[test]
+fn version_dispatch() {
+    mock::set_map_size(1, 1, 1);
+    let turf = byond_locatexyz(ByondXYZ::with_coords((1, 1, 1))).unwrap();
+
+    mock::set_version(516, 1648);
+    assert!(byond().supports(Feature::PixLoc));
+    assert!(!byond().supports(Feature::SetStrId));
+    assert!(byond_pixloc(turf).is_ok());
+    let id = byond_string::str_id_of("meow").unwrap();
+    assert!(matches!(
+        ByondValue::null().set_strid(id),
+        Err(Error::NotAvailableForThisByondVersion)
+    ));
+
+    mock::set_version(515, 1621);
+    assert_eq!(byond().get_version(), (515, 1621));
+    assert!(!byond().supports(Feature::Crash));
+    assert!(matches!(
+        byond_pixloc(turf),
+        Err(Error::NotAvailableForThisByondVersion)
+    ));
+
+    // Bind errors go to {libname}_stack_trace when there's no Byond_CRASH, libname is byondapi
+    // here as nothing set it. Without the proc they're only logged.
+    let meow = ByondValue::try_from("meow").unwrap();
+    let result = mock::call_ffi(mock_repeat_ffi, &[meow, (-1.0).into()]).unwrap();
+    assert!(result.is_null());
+
+    let traces = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let traces_ = traces.clone();
+    mock::register_global_proc("byondapi_stack_trace", move |args| {
+        traces_.borrow_mut().push(args[0].get_string()?);
+        Ok(ByondValue::null())
+    });
+    let result = mock::call_ffi(mock_repeat_ffi, &[meow, (-1.0).into()]).unwrap();
+    assert!(result.is_null());
+    assert_eq!(traces.borrow().len(), 1);
+    assert!(traces.borrow()[0].contains("Bad argument #2 (times)"));
+}
+
+#[test]
+fn panics() {
+    mock::set_version(516, 1651);
+    assert!(mock::call_ffi(mock_panic_ffi, &[0.0.into()])
+        .unwrap()
+        .is_null());
+
+    let error = mock::call_ffi(mock_panic_ffi, &[1.0.into()]).unwrap_err();
+    assert!(error.starts_with("Rust panicked at"), "{error}");
+    assert!(error.contains("tests/mock.rs"), "{error}");
+    assert!(error.contains("index out of bounds"), "{error}");
+
+    let error = mock::call_ffi(mock_panic_raw_ffi, &[ByondValue::null(); 2]).unwrap_err();
+    assert!(error.ends_with(": 2 args"), "{error}");
+
+    let panic = std::panic::catch_unwind(|| mock::call_ffi(mock_no_catch_ffi, &[])).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"meow"));
+}
+
+/// Polls the job like the generated DM does
+fn await_job(job: ByondValue) -> Result<ByondValue, String> {
+    for _ in 0..1000 {
+        let result = mock::call_ffi(byondapi::jobs::byondapi_poll_job, &[job])?;
+        if !result.is_null() {
+            return Ok(result.get_list_values().unwrap()[0]);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("job never finished")
+}
+
+#[test]
+fn async_binds() {
+    mock::set_version(516, 1651);
+    let meow = ByondValue::try_from("meow").unwrap();
+
+    let job = mock::call_ffi(mock_async_ffi, &[meow, 2.0.into()]).unwrap();
+    assert!(job.is_num());
+    let result = await_job(job).unwrap();
+    assert_eq!(result.get_string().unwrap(), "meowmeow");
+    // Finished jobs are forgotten once they're polled
+    assert!(await_job(job).unwrap_err().contains("No job with id"));
+
+    let job = mock::call_ffi(mock_async_ffi, &[meow, 0.0.into()]).unwrap();
+    assert!(await_job(job)
+        .unwrap_err()
+        .contains("Cannot convert value to target type"));
+
+    // Arguments are still converted right away
+    let error = mock::call_ffi(mock_async_ffi, &[meow]).unwrap_err();
+    assert!(error.contains("Bad argument #2 (times)"));
+
+    // More jobs than job threads queue up, each keeps its own id
+    let jobs: Vec<_> = (1..=32)
+        .map(|times| mock::call_ffi(mock_async_ffi, &[meow, (times as f32).into()]).unwrap())
+        .collect();
+    let mut ids: Vec<_> = jobs
+        .iter()
+        .map(|job| job.get_number().unwrap() as u32)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 32);
+    for (times, job) in (1..=32).zip(jobs) {
+        let result = await_job(job).unwrap();
+        assert_eq!(result.get_string().unwrap().len(), 4 * times);
+    }
+
+    // Runtimes fail the job without taking its worker thread down
+    let threads = std::thread::available_parallelism().unwrap().get();
+    for _ in 0..=threads {
+        let job = mock::call_ffi(mock_async_runtime_ffi, &[]).unwrap();
+        assert!(await_job(job).unwrap_err().contains("out of cheese"));
+    }
+    let job = mock::call_ffi(mock_async_ffi, &[meow, 1.0.into()]).unwrap();
+    assert_eq!(await_job(job).unwrap().get_string().unwrap(), "meow");
+}
+
+#[test]
+fn executor() {
+    use byondapi::executor::{block_on, byond_main, spawn};
+
+    let worker = std::thread::spawn(|| block_on(async { byond_main(|| 21 * 2).await }));
+    assert_eq!(worker.join().unwrap(), 42);
+
+    let worker = std::thread::spawn(|| block_on(byond_main(|| -> u32 { panic!("meow") })));
+    let panic = worker.join().unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"meow"));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    spawn(async move {
+        let first = byond_main(|| 1).await;
+        let second = byond_main(|| 2).await;
+        sender.send(first + second).unwrap();
+    });
+    assert_eq!(receiver.recv().unwrap(), 3);
+}
+
+#[test]
+fn typed_thread_sync() {
+    use byondapi::threadsync::{thread_sync_detached, thread_sync_typed};
+
+    let worker = std::thread::spawn(|| thread_sync_typed(|| vec![1, 2, 3]));
+    assert_eq!(worker.join().unwrap().unwrap(), vec![1, 2, 3]);
+
+    let error = thread_sync_typed(|| -> u32 { panic!("meow") }).unwrap_err();
+    assert!(matches!(&error, Error::ThreadSyncPanicked(message) if message.ends_with("meow")));
+
+    let handle = thread_sync_detached(|| "detached".to_owned());
+    assert!(handle.is_finished());
+    assert_eq!(handle.wait().unwrap(), "detached");
+}
+
+#[test]
+fn persistent_refs() {
+    use byondapi::value::refcounted::PersistentRef;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PersistentRef>();
+
+    let obj = new_obj("/obj");
+    let first = PersistentRef::new(obj);
+    assert_eq!(obj.get_refcount().unwrap(), 1);
+    let second = first.clone();
+    assert_eq!(obj.get_refcount().unwrap(), 2);
+    assert_eq!(second.get(), obj);
+    drop(first);
+    assert_eq!(obj.get_refcount().unwrap(), 1);
+
+    assert!(second.is_valid());
+    byondapi::mock::delete(&obj);
+    assert!(!second.is_valid());
+}
+
+#[test]
+fn weak_refs() {
+    use byondapi::value::weak::WeakByondValue;
+
+    mock::register_type("/obj/weak", &[("weak_stamp", ByondValue::null())]);
+    let obj = new_obj("/obj/weak");
+    let weak = WeakByondValue::new(obj, "weak_stamp").unwrap();
+    assert_eq!(weak.upgrade(), Some(obj));
+    // Later weak references keep the stamp
+    let again = WeakByondValue::new(obj, "weak_stamp").unwrap();
+    assert!(!weak.is_stale() && !again.is_stale());
+    byondapi::mock::delete(&obj);
+    assert_eq!(weak.upgrade(), None);
+
+    // The next datum of the same type gets the deleted one's ref
+    let reused = new_obj("/obj/weak");
+    assert_eq!(reused, obj);
+    assert_eq!(weak.upgrade(), None);
+    let weak_reused = WeakByondValue::new(reused, "weak_stamp").unwrap();
+    assert_eq!(weak_reused.upgrade(), Some(reused));
+    assert!(weak.is_stale());
+
+    // Vars holding something else aren't stamped over
+    let mut obj = new_obj("/obj");
+    obj.write_var("tag", &ByondValue::new_str("first").unwrap())
+        .unwrap();
+    assert!(WeakByondValue::new(obj, "tag").is_err());
+
+    // A different tag stands in for another datum having taken over the ref id
+    let weak = WeakByondValue::with_marker(obj, "tag").unwrap();
+    assert!(!weak.is_stale());
+    obj.write_var("tag", &ByondValue::new_str("second").unwrap())
+        .unwrap();
+    assert!(weak.is_stale());
+
+    assert!(matches!(
+        WeakByondValue::new(ByondValue::new_num(1.), "weak_stamp"),
+        Err(Error::UnexpectedType { .. })
+    ));
+}
+
+#[test]
+fn turf_refs() {
+    use std::collections::HashSet;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<TurfRef>();
+
+    mock::set_map_size(3, 3, 1);
+    let turf = Turf::at(ByondXYZ::with_coords((2, 3, 1))).unwrap().unwrap();
+    let turf_ref = TurfRef::from_byond(&turf).unwrap();
+    assert_eq!(turf_ref.coords().coordinates(), (2, 3, 1));
+    assert_eq!(turf_ref.to_byond().unwrap(), turf.into_value());
+    let grid: HashSet<TurfRef> = [turf_ref, TurfRef::try_from(turf).unwrap()].into();
+    assert_eq!(grid.len(), 1);
+
+    mock::set_map_size(5, 5, 1);
+    let resized = Turf::at(ByondXYZ::with_coords((2, 3, 1))).unwrap().unwrap();
+    assert_eq!(turf_ref.resolve().unwrap(), Some(resized));
+    assert!(grid.contains(&TurfRef::from_byond(&resized).unwrap()));
+
+    mock::set_map_size(1, 1, 1);
+    assert_eq!(turf_ref.resolve().unwrap(), None);
+    assert!(turf_ref.to_byond().unwrap().is_null());
+    assert!(TurfRef::from_byond(&new_obj("/obj")).is_err());
+}
+
+#[test]
+fn temp_scopes() {
+    use byondapi::value::{refcounted::PersistentRef, temp::TempScope};
+
+    mock::set_version(516, 1651);
+    let obj = new_obj("/obj");
+    let items = ["a", "b"].map(|item| ByondValue::new_str(item).unwrap());
+    let list = ByondValue::try_from(items.as_slice()).unwrap();
+    mock::take_released_temp_refs();
+
+    let kept = TempScope::run(|scope| {
+        let name = obj.read_var("name").unwrap();
+        let items = list.get_list_values().unwrap();
+        assert_eq!(scope.len(), 3);
+        scope.keep(&name);
+        items[0]
+    });
+    assert_eq!(kept.get_string().unwrap(), "a");
+    let released = mock::take_released_temp_refs();
+    assert_eq!(released.len(), 2);
+    assert_eq!(released[0].get_string().unwrap(), "a");
+
+    let outer = TempScope::new();
+    obj.read_var("name").unwrap();
+    {
+        let inner = TempScope::new();
+        obj.read_var("name").unwrap();
+        ByondValue::new_num(1.).to_byond().unwrap();
+        assert_eq!(inner.len(), 1);
+    }
+    assert_eq!(mock::take_released_temp_refs().len(), 1);
+    assert_eq!(outer.len(), 1);
+    drop(outer);
+    assert_eq!(mock::take_released_temp_refs().len(), 1);
+
+    obj.read_var("name").unwrap();
+    assert!(mock::take_released_temp_refs().is_empty());
+
+    // 515 releases temporary refs with DecRef, which would take the persistent ref instead
+    mock::set_version(515, 1621);
+    let persistent = TempScope::run(|scope| {
+        let persistent = PersistentRef::new(new_obj("/obj"));
+        assert!(scope.is_empty());
+        persistent
+    });
+    assert_eq!(persistent.get_refcount().unwrap(), 1);
+    assert!(mock::take_released_temp_refs().is_empty());
+}
+
+#[cfg(feature = "ref-tracking")]
+#[test]
+fn ref_tracking() {
+    use byondapi::{debug, value::refcounted::PersistentRef};
+
+    // Other tests run at the same time, only look at refs made in here
+    let made_here = || {
+        debug::outstanding_refs()
+            .into_iter()
+            .filter(|site| site.backtrace.contains("ref_tracking"))
+            .map(|site| site.values.len())
+            .sum::<usize>()
+    };
+
+    let obj = new_obj("/obj");
+    let kept = PersistentRef::new(obj);
+    let dropped = kept.clone();
+    assert_eq!(made_here(), 2);
+    drop(dropped);
+    assert_eq!(made_here(), 1);
+    assert!(debug::outstanding_refs_report().contains("ref_tracking"));
+
+    // A reference byondapi-rs didn't make doesn't take the tracked one with it
+    unsafe { byondapi::byond().ByondValue_IncRef(&obj.0) };
+    let mut extra = obj;
+    extra.decrement_untracked_ref();
+    assert_eq!(made_here(), 1);
+    drop(kept);
+    assert_eq!(made_here(), 0);
+}
+
+#[test]
+fn error_context() {
+    use byondapi::error::ResultExt;
+
+    let obj = new_obj("/obj");
+    let error = obj.call("no_such_proc", &[]).unwrap_err();
+    assert!(matches!(error.root(), Error::InvalidProc(_)));
+    assert_eq!(error.contexts(), ["calling proc 'no_such_proc' on /obj"]);
+
+    let error = Err::<(), _>(error)
+        .context("updating the thing")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot call proc \"no_such_proc\", proc doesn't exist
+    while calling proc 'no_such_proc' on /obj
+    while updating the thing"
+    );
+
+    let error = ByondValue::new_num(1.).read_var("name").unwrap_err();
+    assert!(matches!(error.root(), Error::NotReferencable(_)));
+    assert_eq!(
+        error.contexts(),
+        [r#"reading var 'name' on ByondValue("Number", "1")"#]
+    );
+
+    // Binds runtime with the whole chain
+    mock::set_version(516, 1651);
+    let runtime = mock::call_ffi(mock_get_name_ffi, &[obj]).unwrap_err();
+    assert!(runtime.ends_with("\n    while calling proc 'get_name' on /obj"));
+}
+
+#[test]
+fn exceptions() {
+    mock::set_version(516, 1651);
+    mock::register_type("/exception/cheese", &[("wedges", ByondValue::null())]);
+    let raised = std::rc::Rc::new(std::cell::Cell::new(ByondValue::null()));
+    let raised_ = raised.clone();
+    // No libname was registered, so the generated procs go by byondapi_
+    mock::register_global_proc("byondapi_raise", move |args| {
+        raised_.set(args[0]);
+        Ok(ByondValue::null())
+    });
+
+    let result = mock::call_ffi(mock_throw_ffi, &[false.into()]).unwrap();
+    assert!(result.is_null());
+    let exception = Datum::try_from(raised.get()).unwrap();
+    assert_eq!(exception.type_path().unwrap(), "/exception/cheese");
+    assert_eq!(exception.read_string("name").unwrap(), "Out of cheese");
+    assert_eq!(exception.read_string("desc").unwrap(), "Redo from start");
+    assert!(exception.read_string("file").unwrap().ends_with("mock.rs"));
+    assert!(exception.read_number("line").unwrap() > 0.);
+    assert_eq!(exception.read_number("wedges").unwrap(), 0.);
+
+    mock::call_ffi(mock_throw_ffi, &[true.into()]).unwrap();
+    let exception = raised.get();
+    assert_eq!(
+        exception.read_string("desc").unwrap(),
+        "Redo from start\n    while making a sandwich"
+    );
+
+    // Exceptions are found in eyre reports and boxed errors too
+    raised.set(ByondValue::null());
+    mock::call_ffi(mock_throw_eyre_ffi, &[]).unwrap();
+    assert_eq!(raised.get().read_string("name").unwrap(), "Out of cheese");
+    raised.set(ByondValue::null());
+    mock::call_ffi(mock_throw_boxed_ffi, &[]).unwrap();
+    assert_eq!(
+        raised.get().read_string("desc").unwrap(),
+        "while making a sandwich"
+    );
+
+    // Other errors runtime
+    raised.set(ByondValue::null());
+    let runtime = mock::call_ffi(mock_throw_str_ffi, &[]).unwrap_err();
+    assert_eq!(runtime, "Out of cheese");
+    assert!(raised.get().is_null());
+
+    // A #define has no wrapper to throw from, so it runtimes instead
+    raised.set(ByondValue::null());
+    let runtime = mock::call_ffi(mock_throw_macro_ffi, &[]).unwrap_err();
+    assert_eq!(runtime, "Out of cheese");
+    assert!(raised.get().is_null());
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn logging() {
+    use byondapi::{
+        error::crash_logging,
+        logging::{self, byondapi_set_log_level, byondapi_set_log_proc},
+    };
+
+    let dir = std::env::temp_dir().join(format!("byondapi-logging-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("log.txt");
+    crash_logging::set_log_file(crash_logging::LogFile {
+        path: path.clone(),
+        max_size: 64,
+        keep: 2,
+    });
+
+    byond();
+    logging::init_log().unwrap();
+    let records = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    // {libname}_log, going by byondapi_ without a registered libname
+    for proc_name in ["byondapi_log", "my_log"] {
+        let records = records.clone();
+        mock::register_global_proc(proc_name, move |args| {
+            let args: Vec<_> = args.iter().map(|arg| arg.get_string().unwrap()).collect();
+            records
+                .borrow_mut()
+                .push(format!("{proc_name} {}", args.join(" ")));
+            Ok(ByondValue::null())
+        });
+    }
+
+    log::info!("hello {}", 1);
+    log::debug!("too detailed");
+    assert_eq!(records.take(), ["byondapi_log info mock hello 1"]);
+
+    let set_level = |level: &str| {
+        mock::call_ffi(byondapi_set_log_level, &[level.try_into().unwrap()])
+            .unwrap()
+            .get_bool()
+            .unwrap()
+    };
+    assert!(set_level("debug"));
+    assert!(!set_level("loud"));
+    log::debug!("detailed");
+    assert!(set_level("error"));
+    log::warn!("not bad enough");
+    assert_eq!(records.take(), ["byondapi_log debug mock detailed"]);
+    assert!(set_level("info"));
+
+    let set_proc = |name: ByondValue| mock::call_ffi(byondapi_set_log_proc, &[name]).unwrap();
+    set_proc("/proc/my_log".try_into().unwrap());
+    log::info!("somewhere else");
+    set_proc(ByondValue::null());
+    log::info!("back again");
+    assert_eq!(
+        records.take(),
+        [
+            "my_log info mock somewhere else",
+            "byondapi_log info mock back again"
+        ]
+    );
+
+    // Records that can't be sent go to the file, which is appended to and rotated
+    set_proc("missing_proc".try_into().unwrap());
+    log::info!("lost");
+    set_proc(ByondValue::null());
+    let logged = std::fs::read_to_string(&path).unwrap();
+    assert!(logged.contains("[info] mock: lost\n"));
+    for line in 0..10 {
+        crash_logging::log_to_file(format!("line {line}"));
+    }
+    let rotated = |index: usize| dir.join(format!("log.txt.{index}"));
+    assert!(rotated(1).exists() && rotated(2).exists() && !rotated(3).exists());
+    assert!(std::fs::read_to_string(&path).unwrap().contains("line 9\n"));
+    _ = std::fs::remove_dir_all(&dir);
+}
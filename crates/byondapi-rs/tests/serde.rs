@@ -0,0 +1,90 @@
+//! `cargo test --package byondapi --features mock,serde`
+#![cfg(all(feature = "mock", feature = "serde"))]
+
+use std::collections::HashMap;
+
+use byondapi::{
+    mock,
+    prelude::*,
+    value::serde::{from_value, to_value},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle(f32),
+    Rect { w: u32, h: u32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Config {
+    name: String,
+    ratio: f32,
+    retries: u8,
+    enabled: bool,
+    motd: Option<String>,
+    tags: Vec<String>,
+    limits: HashMap<String, i32>,
+    shapes: Vec<Shape>,
+    pair: (u16, String),
+}
+
+#[test]
+fn round_trip() {
+    let config = Config {
+        name: "station".to_owned(),
+        ratio: 0.5,
+        retries: 3,
+        enabled: true,
+        motd: None,
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        limits: HashMap::from([("players".to_owned(), 80), ("admins".to_owned(), -1)]),
+        shapes: vec![Shape::Point, Shape::Circle(2.0), Shape::Rect { w: 3, h: 4 }],
+        pair: (7, "seven".to_owned()),
+    };
+    let value = to_value(&config).unwrap();
+    assert!(value.is_list());
+    assert_eq!(
+        value.read_list_index("name").unwrap().get_string().unwrap(),
+        "station"
+    );
+    assert_eq!(from_value::<Config>(&value).unwrap(), config);
+}
+
+#[test]
+fn from_dm_values() {
+    assert!(!from_value::<bool>(&ByondValue::null()).unwrap());
+    assert!(from_value::<bool>(&5.0.into()).unwrap());
+    assert_eq!(from_value::<Option<u8>>(&ByondValue::null()).unwrap(), None);
+    assert!(from_value::<u8>(&300.0.into()).is_err());
+    assert!(from_value::<u8>(&1.5.into()).is_err());
+    assert!(from_value::<String>(&1.0.into()).is_err());
+
+    mock::register_type(
+        "/datum/limits",
+        &[("players", 40.0.into()), ("admins", 3.0.into())],
+    );
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Limits {
+        players: u32,
+        admins: u32,
+        #[serde(default)]
+        ghosts: u32,
+    }
+    let datum =
+        ByondValue::builtin_new(ByondValue::new_str("/datum/limits").unwrap(), &[]).unwrap();
+    assert_eq!(
+        from_value::<Limits>(&datum).unwrap(),
+        Limits {
+            players: 40,
+            admins: 3,
+            ghosts: 0
+        }
+    );
+}
+
+#[test]
+fn number_keys() {
+    assert!(to_value(&HashMap::from([(1, "one")])).is_err());
+}
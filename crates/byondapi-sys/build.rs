@@ -54,7 +54,7 @@ fn generate_all() {
             let builder = bindgen::Builder::default()
                 .header(wrapper.to_string_lossy())
                 .dynamic_library_name("ByondApi")
-                .dynamic_link_require_all(true)
+                .dynamic_link_require_all(false)
                 .override_abi(Abi::CUnwind, "Byond.*")
                 // Also make headers included by main header dependencies of the build
                 .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
@@ -5,26 +5,31 @@
     non_camel_case_types,
     non_snake_case
 )]
+#[cfg(not(feature = "mock"))]
 use std::ops::Deref;
 
-#[cfg(all(not(target_pointer_width = "32"), not(feature = "opendream")))]
+#[cfg(all(
+    not(target_pointer_width = "32"),
+    not(any(feature = "opendream", feature = "mock"))
+))]
 compile_error!("BYOND API only functions with 32-bit targets");
 
-#[cfg(all(not(target_arch = "x86"), not(feature = "opendream")))]
+#[cfg(all(
+    not(target_arch = "x86"),
+    not(any(feature = "opendream", feature = "mock"))
+))]
 compile_error!("BYOND API only functions on x86 targets");
 
 #[cfg(all(
     not(any(target_os = "linux", target_os = "windows")),
-    not(feature = "opendream")
+    not(any(feature = "opendream", feature = "mock"))
 ))]
 compile_error!("BYOND API only supports Windows and Linux");
 
 // Include byondapi-c bindings (generated by build.rs)
+// Always the newest header, functions older versions don't have are only looked up, see `Feature`
 #[allow(dead_code, rustdoc::broken_intra_doc_links)]
 mod byond_rawbind {
-    #[cfg(feature = "byond-515-1621")]
-    include!(concat!(env!("OUT_DIR"), "/bindings_515_1621.rs"));
-    #[cfg(feature = "byond-516-1651")]
     include!(concat!(env!("OUT_DIR"), "/bindings_516_1651.rs"));
 }
 
@@ -32,6 +37,7 @@ mod byond_rawbind {
 pub use byond_rawbind::ByondApi as RawByondApi;
 
 /// we must simply hope this never changes
+#[cfg(not(feature = "mock"))]
 mod version {
     use super::byond_rawbind::u4c;
 
@@ -50,14 +56,47 @@ mod version {
     }
 }
 
+/// Parts of byondapi that only newer versions of BYOND have, see [`ByondApi::supports`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// `Byond_PixLoc` and `Byond_BoundPixLoc`
+    PixLoc,
+    /// `Byond_CRASH`
+    Crash,
+    /// `ByondValue_SetStrId`
+    SetStrId,
+    /// `ByondValue_DecTempRef`, which comes with the reworked reference counting
+    DecTempRef,
+}
+
+impl Feature {
+    /// The first BYOND version that has this feature, as `(major, build)`
+    pub fn min_version(self) -> (u32, u32) {
+        match self {
+            Feature::PixLoc | Feature::Crash => (516, 1648),
+            Feature::SetStrId | Feature::DecTempRef => (516, 1651),
+        }
+    }
+}
+
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(feature = "mock")]
+pub use mock::ByondApi;
+
+#[cfg(not(feature = "mock"))]
 pub struct ByondApi {
     internal: byond_rawbind::ByondApi,
     version: (u32, u32),
 }
 
+#[cfg(not(feature = "mock"))]
 unsafe impl Sync for ByondApi {}
+#[cfg(not(feature = "mock"))]
 unsafe impl Send for ByondApi {}
 
+#[cfg(not(feature = "mock"))]
 impl ByondApi {
     pub unsafe fn init_from_library<L>(library: L) -> Result<ByondApi, libloading::Error>
     where
@@ -74,8 +113,20 @@ impl ByondApi {
     pub fn get_version(&self) -> (u32, u32) {
         self.version
     }
+
+    /// Whether the loaded byondcore has `feature`. Calling a function it doesn't have panics.
+    pub fn supports(&self, feature: Feature) -> bool {
+        let raw = &self.internal;
+        match feature {
+            Feature::PixLoc => raw.Byond_PixLoc.is_ok() && raw.Byond_BoundPixLoc.is_ok(),
+            Feature::Crash => raw.Byond_CRASH.is_ok(),
+            Feature::SetStrId => raw.ByondValue_SetStrId.is_ok(),
+            Feature::DecTempRef => raw.ByondValue_DecTempRef.is_ok(),
+        }
+    }
 }
 
+#[cfg(not(feature = "mock"))]
 impl Deref for ByondApi {
     type Target = byond_rawbind::ByondApi;
 
@@ -104,7 +155,6 @@ pub use byond_rawbind::u8c;
 pub use byond_rawbind::ByondCallback;
 pub use byond_rawbind::ByondValueData;
 pub use byond_rawbind::ByondValueType;
-#[cfg(feature = "byond-516-1651")]
 pub use byond_rawbind::CByondPixLoc;
 pub use byond_rawbind::CByondValue;
 pub use byond_rawbind::CByondXYZ;
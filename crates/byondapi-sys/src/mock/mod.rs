@@ -0,0 +1,653 @@
+//! Pure-rust stand-in for byondcore, enabled with the `mock` feature.
+//!
+//! [`ByondApi`] has the same function table as the bindgen generated one, but everything is
+//! served from a fake world kept in a thread local, so every test thread gets its own. The string
+//! tree is the exception, it's shared by all threads so cached string ids stay valid.
+//!
+//! Known differences from the real thing:
+//! - Procs only exist if they're registered with [`register_proc`] or [`register_global_proc`]
+//! - `new` only takes type paths as strings, and objects only have vars registered with
+//!   [`register_type`] plus a few builtins (`tag`, `type`, `name`/`loc` for atoms and
+//!   `client`/`ckey`/`key` for mobs). There are no clients.
+//! - `vars` is a plain list holding a copy of the vars, writing to it doesn't change the object
+//! - There's no garbage collection, objects live until [`delete`] is called on them
+//! - [`ByondApi::Byond_ThreadSync`] runs the callback right away on the calling thread
+//! - [`ByondApi::Byond_CRASH`] panics with a [`Runtime`] payload instead of longjumping
+//! - Functions newer than the version set with [`set_version`] can still be called
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+mod world;
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    rc::Rc,
+};
+
+use world::{get_num, get_ref, null, num, value, with_world, Resolved, NONE};
+
+use crate::{
+    u1c, u4c, ByondCallback, ByondValueType, CByondPixLoc, CByondValue, CByondXYZ, Feature,
+};
+
+/// Payload of the panic raised by [`ByondApi::Byond_CRASH`], holds the runtime message
+#[derive(Debug, Clone)]
+pub struct Runtime(pub String);
+
+/// Empties this thread's world, everything but the string tree is forgotten.
+pub fn reset() {
+    world::reset()
+}
+
+/// Changes the BYOND version this thread's world reports, which decides what
+/// [`ByondApi::supports`] answers. Worlds start out as 516.1651, or 515.1621 with the
+/// `byond-515-1621` feature.
+pub fn set_version(major: u4c, build: u4c) {
+    with_world(|world| world.version = (major, build))
+}
+
+/// Equivalent to setting `world.maxx`, `world.maxy` and `world.maxz`, existing turf refs become
+/// invalid.
+pub fn set_map_size(x: i16, y: i16, z: i16) {
+    with_world(|world| world.set_map_size(x, y, z))
+}
+
+/// Defines the vars (and their initial values) objects of this type get on `new`, on top of what
+/// parent types define.
+pub fn register_type(path: &str, vars: &[(&str, CByondValue)]) {
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (world::intern_str(name), *value))
+        .collect();
+    with_world(|world| world.types.insert(path.to_owned(), vars));
+}
+
+/// Registers `proc` as `{type_path}/proc/{name}`, callable on `type_path` and all its subtypes.
+pub fn register_proc<F>(type_path: &str, name: &str, proc: F)
+where
+    F: Fn(CByondValue, &[CByondValue]) -> Result<CByondValue, String> + 'static,
+{
+    let name = world::intern_str(name);
+    with_world(|world| {
+        world
+            .procs
+            .insert((type_path.to_owned(), name), Rc::new(proc))
+    });
+}
+
+/// Registers `proc` as `/proc/{name}`
+pub fn register_global_proc<F>(name: &str, proc: F)
+where
+    F: Fn(&[CByondValue]) -> Result<CByondValue, String> + 'static,
+{
+    let name = world::intern_str(name);
+    with_world(|world| {
+        world
+            .global_procs
+            .insert(name, Rc::new(move |_, args| proc(args)))
+    });
+}
+
+/// Equivalent to `&var` in DM, creates a pointer holding `initial`
+pub fn new_pointer(initial: CByondValue) -> CByondValue {
+    with_world(|world| {
+        world.pointers.push(initial);
+        value(world::POINTER, world.pointers.len() as u4c - 1)
+    })
+}
+
+/// Values passed to [`ByondApi::ByondValue_DecTempRef`] since the last call, oldest first
+pub fn take_released_temp_refs() -> Vec<CByondValue> {
+    with_world(|world| std::mem::take(&mut world.released_temp_refs))
+}
+
+/// Equivalent to `del()` in DM, the object is removed and every var or list holding it is
+/// cleared
+pub fn delete(target: &CByondValue) {
+    with_world(|world| world.delete(target))
+}
+
+fn fail<S: Into<String>>(error: S) -> bool {
+    with_world(|world| world.set_error(error));
+    false
+}
+
+fn finish<T>(result: Result<T, String>, out: impl FnOnce(T)) -> bool {
+    match result {
+        Ok(value) => {
+            out(value);
+            true
+        }
+        Err(error) => fail(error),
+    }
+}
+
+/// Follows byondcore's buffer protocol: if `len` is too small, it's set to the required length
+/// and false is returned
+unsafe fn copy_out(values: &[CByondValue], buffer: *mut CByondValue, len: *mut u4c) -> bool {
+    if (*len as usize) < values.len() || (buffer.is_null() && !values.is_empty()) {
+        *len = values.len() as u4c;
+        return false;
+    }
+    if !values.is_empty() {
+        std::ptr::copy_nonoverlapping(values.as_ptr(), buffer, values.len());
+    }
+    *len = values.len() as u4c;
+    true
+}
+
+unsafe fn args<'a>(arg: *const CByondValue, arg_count: u4c) -> &'a [CByondValue] {
+    if arg.is_null() || arg_count == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(arg, arg_count as usize)
+}
+
+fn call(
+    proc: Result<Resolved, String>,
+    src: CByondValue,
+    args: &[CByondValue],
+) -> Result<CByondValue, String> {
+    proc.and_then(|proc| match proc {
+        Resolved::Builtin(result) => Ok(result),
+        // The world isn't borrowed anymore, so the proc is free to call back into the api
+        Resolved::Rust(proc) => proc(src, args),
+    })
+}
+
+fn new_object(path: &CByondValue, args: &[CByondValue]) -> Result<CByondValue, String> {
+    if path.type_ != world::STRING {
+        return Err("the mock can only create objects from type paths as strings".to_owned());
+    }
+    let path = world::string(get_ref(path)).unwrap_or_default();
+    let created = with_world(|world| world.new_object(&path.to_string_lossy()))?;
+    let new_proc = with_world(|world| world.resolve_proc(&created, world::intern_str("New"), &[]));
+    if created.type_ == world::LIST {
+        if let Some(size) = args.first().and_then(get_num) {
+            with_world(|world| -> Result<(), String> {
+                world.list_mut(&created)?.items = vec![(null(), null()); size as usize];
+                Ok(())
+            })?;
+        }
+    } else if let Ok(Resolved::Rust(proc)) = new_proc {
+        proc(created, args)?;
+    }
+    Ok(created)
+}
+
+/// Fake function table, see the [module docs](self)
+pub struct ByondApi {
+    _private: (),
+}
+
+impl ByondApi {
+    pub fn init_mock() -> ByondApi {
+        ByondApi { _private: () }
+    }
+
+    pub fn get_version(&self) -> (u32, u32) {
+        with_world(|world| world.version)
+    }
+
+    /// Whether the version set with [`set_version`] has `feature`. Every function is still
+    /// callable regardless.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.get_version() >= feature.min_version()
+    }
+
+    pub unsafe fn Byond_LastError(&self) -> *const c_char {
+        with_world(|world| {
+            world
+                .last_error
+                .as_ref()
+                .map_or(std::ptr::null(), |error| error.as_ptr())
+        })
+    }
+
+    pub unsafe fn Byond_GetVersion(&self, version: *mut u4c, build: *mut u4c) {
+        (*version, *build) = self.get_version();
+    }
+
+    pub unsafe fn Byond_GetDMBVersion(&self) -> u4c {
+        self.get_version().0
+    }
+
+    pub unsafe fn ByondValue_Clear(&self, v: *mut CByondValue) {
+        *v = null();
+    }
+
+    pub unsafe fn ByondValue_Type(&self, v: *const CByondValue) -> ByondValueType {
+        (*v).type_
+    }
+
+    pub unsafe fn ByondValue_IsNull(&self, v: *const CByondValue) -> bool {
+        (*v).type_ == world::NULL
+    }
+
+    pub unsafe fn ByondValue_IsNum(&self, v: *const CByondValue) -> bool {
+        (*v).type_ == world::NUMBER
+    }
+
+    pub unsafe fn ByondValue_IsStr(&self, v: *const CByondValue) -> bool {
+        (*v).type_ == world::STRING
+    }
+
+    pub unsafe fn ByondValue_IsList(&self, v: *const CByondValue) -> bool {
+        world::is_list_type((*v).type_)
+    }
+
+    pub unsafe fn ByondValue_IsTrue(&self, v: *const CByondValue) -> bool {
+        match (*v).type_ {
+            world::NULL => false,
+            world::NUMBER => get_num(&*v) != Some(0.0),
+            world::STRING => world::string(get_ref(&*v)).is_some_and(|s| !s.is_empty()),
+            _ => true,
+        }
+    }
+
+    pub unsafe fn ByondValue_GetNum(&self, v: *const CByondValue) -> f32 {
+        get_num(&*v).unwrap_or_default()
+    }
+
+    pub unsafe fn ByondValue_GetRef(&self, v: *const CByondValue) -> u4c {
+        match (*v).type_ {
+            world::NULL | world::NUMBER => 0,
+            _ => get_ref(&*v),
+        }
+    }
+
+    pub unsafe fn ByondValue_SetNum(&self, v: *mut CByondValue, f: f32) {
+        *v = num(f);
+    }
+
+    pub unsafe fn ByondValue_SetStr(&self, v: *mut CByondValue, str_: *const c_char) {
+        *v = value(world::STRING, world::intern(CStr::from_ptr(str_)));
+    }
+
+    pub unsafe fn ByondValue_SetStrId(&self, v: *mut CByondValue, strid: u4c) {
+        let strid = if strid == NONE { 0 } else { strid };
+        *v = value(world::STRING, strid);
+    }
+
+    pub unsafe fn ByondValue_SetRef(&self, v: *mut CByondValue, type_: ByondValueType, ref_: u4c) {
+        *v = value(type_, ref_);
+    }
+
+    pub unsafe fn ByondValue_Equals(&self, a: *const CByondValue, b: *const CByondValue) -> bool {
+        world::same(&*a, &*b)
+    }
+
+    pub unsafe fn Byond_ThreadSync(
+        &self,
+        callback: ByondCallback,
+        data: *mut c_void,
+        block: bool,
+    ) -> CByondValue {
+        let result = callback.map_or_else(null, |callback| callback(data));
+        if block {
+            result
+        } else {
+            null()
+        }
+    }
+
+    pub unsafe fn Byond_GetStrId(&self, str_: *const c_char) -> u4c {
+        world::lookup(CStr::from_ptr(str_)).unwrap_or(NONE)
+    }
+
+    pub unsafe fn Byond_AddGetStrId(&self, str_: *const c_char) -> u4c {
+        world::intern(CStr::from_ptr(str_))
+    }
+
+    pub unsafe fn Byond_ReadVar(
+        &self,
+        loc: *const CByondValue,
+        varname: *const c_char,
+        result: *mut CByondValue,
+    ) -> bool {
+        let varname = world::intern(CStr::from_ptr(varname));
+        self.Byond_ReadVarByStrId(loc, varname, result)
+    }
+
+    pub unsafe fn Byond_ReadVarByStrId(
+        &self,
+        loc: *const CByondValue,
+        varname: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        let value = with_world(|world| world.read_var(&*loc, varname));
+        finish(value, |value| *result = value)
+    }
+
+    pub unsafe fn Byond_WriteVar(
+        &self,
+        loc: *const CByondValue,
+        varname: *const c_char,
+        val: *const CByondValue,
+    ) -> bool {
+        let varname = world::intern(CStr::from_ptr(varname));
+        self.Byond_WriteVarByStrId(loc, varname, val)
+    }
+
+    pub unsafe fn Byond_WriteVarByStrId(
+        &self,
+        loc: *const CByondValue,
+        varname: u4c,
+        val: *const CByondValue,
+    ) -> bool {
+        let written = with_world(|world| world.write_var(&*loc, varname, &*val));
+        finish(written, |_| ())
+    }
+
+    pub unsafe fn Byond_CreateList(&self, result: *mut CByondValue) -> bool {
+        *result = with_world(|world| world.new_list(Vec::new()));
+        true
+    }
+
+    pub unsafe fn Byond_ReadList(
+        &self,
+        loc: *const CByondValue,
+        list: *mut CByondValue,
+        len: *mut u4c,
+    ) -> bool {
+        let items = with_world(|world| {
+            world
+                .list(&*loc)
+                .map(|l| l.items.iter().map(|(item, _)| *item).collect::<Vec<_>>())
+        });
+        match items {
+            Ok(items) => copy_out(&items, list, len),
+            Err(error) => {
+                *len = 0;
+                fail(error)
+            }
+        }
+    }
+
+    pub unsafe fn Byond_WriteList(
+        &self,
+        loc: *const CByondValue,
+        list: *const CByondValue,
+        len: u4c,
+    ) -> bool {
+        let items = args(list, len);
+        let written = with_world(|world| -> Result<(), String> {
+            world.list_mut(&*loc)?.items = items.iter().map(|item| (*item, null())).collect();
+            Ok(())
+        });
+        finish(written, |_| ())
+    }
+
+    pub unsafe fn Byond_ReadListAssoc(
+        &self,
+        loc: *const CByondValue,
+        list: *mut CByondValue,
+        len: *mut u4c,
+    ) -> bool {
+        let items = with_world(|world| {
+            world.list(&*loc).map(|l| {
+                l.items
+                    .iter()
+                    .flat_map(|(key, value)| [*key, *value])
+                    .collect::<Vec<_>>()
+            })
+        });
+        match items {
+            Ok(items) => copy_out(&items, list, len),
+            Err(error) => {
+                *len = 0;
+                fail(error)
+            }
+        }
+    }
+
+    pub unsafe fn Byond_ReadListIndex(
+        &self,
+        loc: *const CByondValue,
+        idx: *const CByondValue,
+        result: *mut CByondValue,
+    ) -> bool {
+        let value = with_world(|world| world.read_list_index(&*loc, &*idx));
+        finish(value, |value| *result = value)
+    }
+
+    pub unsafe fn Byond_WriteListIndex(
+        &self,
+        loc: *const CByondValue,
+        idx: *const CByondValue,
+        val: *const CByondValue,
+    ) -> bool {
+        let written = with_world(|world| world.write_list_index(&*loc, &*idx, &*val));
+        finish(written, |_| ())
+    }
+
+    pub unsafe fn Byond_ReadPointer(
+        &self,
+        ptr: *const CByondValue,
+        result: *mut CByondValue,
+    ) -> bool {
+        let value = with_world(|world| {
+            ((*ptr).type_ == world::POINTER)
+                .then(|| world.pointers.get(get_ref(&*ptr) as usize).copied())
+                .flatten()
+                .ok_or_else(|| "not a pointer".to_owned())
+        });
+        finish(value, |value| *result = value)
+    }
+
+    pub unsafe fn Byond_WritePointer(
+        &self,
+        ptr: *const CByondValue,
+        val: *const CByondValue,
+    ) -> bool {
+        let written = with_world(|world| {
+            ((*ptr).type_ == world::POINTER)
+                .then(|| world.pointers.get_mut(get_ref(&*ptr) as usize))
+                .flatten()
+                .map(|slot| *slot = *val)
+                .ok_or_else(|| "not a pointer".to_owned())
+        });
+        finish(written, |_| ())
+    }
+
+    pub unsafe fn Byond_CallProc(
+        &self,
+        src: *const CByondValue,
+        name: *const c_char,
+        arg: *const CByondValue,
+        arg_count: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        match world::lookup(CStr::from_ptr(name)) {
+            Some(name) => self.Byond_CallProcByStrId(src, name, arg, arg_count, result),
+            None => fail("proc not found"),
+        }
+    }
+
+    pub unsafe fn Byond_CallProcByStrId(
+        &self,
+        src: *const CByondValue,
+        name: u4c,
+        arg: *const CByondValue,
+        arg_count: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        let args = args(arg, arg_count);
+        let proc = with_world(|world| world.resolve_proc(&*src, name, args));
+        finish(call(proc, *src, args), |value| *result = value)
+    }
+
+    pub unsafe fn Byond_CallGlobalProc(
+        &self,
+        name: *const c_char,
+        arg: *const CByondValue,
+        arg_count: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        match world::lookup(CStr::from_ptr(name)) {
+            Some(name) => self.Byond_CallGlobalProcByStrId(name, arg, arg_count, result),
+            None => fail("proc not found"),
+        }
+    }
+
+    pub unsafe fn Byond_CallGlobalProcByStrId(
+        &self,
+        name: u4c,
+        arg: *const CByondValue,
+        arg_count: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        let args = args(arg, arg_count);
+        let proc = with_world(|world| {
+            world
+                .global_procs
+                .get(&name)
+                .cloned()
+                .map(Resolved::Rust)
+                .ok_or_else(|| "undefined proc".to_owned())
+        });
+        finish(call(proc, null(), args), |value| *result = value)
+    }
+
+    pub unsafe fn Byond_ToString(
+        &self,
+        src: *const CByondValue,
+        buf: *mut c_char,
+        buflen: *mut u4c,
+    ) -> bool {
+        let text = with_world(|world| world.to_text(&*src));
+        let text = CString::new(text).unwrap_or_default();
+        let bytes = text.as_bytes_with_nul();
+        if buf.is_null() || (*buflen as usize) < bytes.len() {
+            *buflen = bytes.len() as u4c;
+            return false;
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr().cast(), buf, bytes.len());
+        *buflen = bytes.len() as u4c;
+        true
+    }
+
+    pub unsafe fn Byond_Block(
+        &self,
+        corner1: *const CByondXYZ,
+        corner2: *const CByondXYZ,
+        list: *mut CByondValue,
+        len: *mut u4c,
+    ) -> bool {
+        let turfs = with_world(|world| world.block(&*corner1, &*corner2));
+        copy_out(&turfs, list, len)
+    }
+
+    pub unsafe fn Byond_Length(&self, src: *const CByondValue, result: *mut CByondValue) -> bool {
+        let length = with_world(|world| world.length(&*src));
+        finish(length, |length| *result = num(length as f32))
+    }
+
+    pub unsafe fn Byond_LocateIn(
+        &self,
+        type_: *const CByondValue,
+        list: *const CByondValue,
+        result: *mut CByondValue,
+    ) -> bool {
+        let found = with_world(|world| world.locate(&*type_, list.as_ref()));
+        finish(found, |found| *result = found)
+    }
+
+    pub unsafe fn Byond_LocateXYZ(&self, xyz: *const CByondXYZ, result: *mut CByondValue) -> bool {
+        let xyz = &*xyz;
+        *result = with_world(|world| world.turf_at(xyz.x, xyz.y, xyz.z)).unwrap_or_else(null);
+        true
+    }
+
+    pub unsafe fn Byond_New(
+        &self,
+        type_: *const CByondValue,
+        arg: *const CByondValue,
+        arg_count: u4c,
+        result: *mut CByondValue,
+    ) -> bool {
+        finish(new_object(&*type_, args(arg, arg_count)), |created| {
+            *result = created
+        })
+    }
+
+    pub unsafe fn Byond_NewArglist(
+        &self,
+        type_: *const CByondValue,
+        arglist: *const CByondValue,
+        result: *mut CByondValue,
+    ) -> bool {
+        let arglist = with_world(|world| {
+            world
+                .list(&*arglist)
+                .map(|l| l.items.iter().map(|(item, _)| *item).collect::<Vec<_>>())
+        });
+        let created = arglist.and_then(|arglist| new_object(&*type_, &arglist));
+        finish(created, |created| *result = created)
+    }
+
+    pub unsafe fn Byond_Refcount(&self, src: *const CByondValue, result: *mut u4c) -> bool {
+        let refcount = with_world(|world| {
+            world
+                .refcount_mut(&*src)
+                .map(|count| *count)
+                .ok_or_else(|| "value is not refcounted".to_owned())
+        });
+        finish(refcount, |refcount| *result = refcount)
+    }
+
+    pub unsafe fn Byond_XYZ(&self, src: *const CByondValue, xyz: *mut CByondXYZ) -> bool {
+        let coords = with_world(|world| world.xyz(&*src));
+        finish(coords, |(x, y, z)| *xyz = CByondXYZ { x, y, z, junk: 0 })
+    }
+
+    pub unsafe fn Byond_PixLoc(&self, src: *const CByondValue, pixloc: *mut CByondPixLoc) -> bool {
+        self.Byond_BoundPixLoc(src, 0, pixloc)
+    }
+
+    pub unsafe fn Byond_BoundPixLoc(
+        &self,
+        src: *const CByondValue,
+        dir: u1c,
+        pixloc: *mut CByondPixLoc,
+    ) -> bool {
+        let coords = with_world(|world| world.pixloc(&*src, dir));
+        finish(coords, |(x, y, z)| {
+            *pixloc = CByondPixLoc { x, y, z, junk: 0 }
+        })
+    }
+
+    pub unsafe fn ByondValue_IncRef(&self, src: *const CByondValue) {
+        with_world(|world| {
+            if let Some(count) = world.refcount_mut(&*src) {
+                *count += 1;
+            }
+        })
+    }
+
+    pub unsafe fn ByondValue_DecRef(&self, src: *const CByondValue) {
+        with_world(|world| {
+            if let Some(count) = world.refcount_mut(&*src) {
+                *count = count.saturating_sub(1);
+            }
+        })
+    }
+
+    /// Temporary references aren't tracked, this only records the call for
+    /// [`take_released_temp_refs`]
+    pub unsafe fn ByondValue_DecTempRef(&self, src: *const CByondValue) {
+        with_world(|world| world.released_temp_refs.push(*src))
+    }
+
+    pub unsafe fn Byond_TestRef(&self, src: *mut CByondValue) -> bool {
+        let valid = with_world(|world| world.exists(&*src));
+        if !valid {
+            *src = null();
+        }
+        valid
+    }
+
+    pub unsafe fn Byond_CRASH(&self, message: *const c_char) {
+        let message = CStr::from_ptr(message).to_string_lossy().into_owned();
+        std::panic::panic_any(Runtime(message))
+    }
+}
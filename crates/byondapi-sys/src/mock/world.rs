@@ -0,0 +1,955 @@
+//! The state behind the fake function table: a string tree, datums with vars, lists, pointers and
+//! a map of turfs.
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    rc::Rc,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{u1c, u2c, u4c, ByondValueData, ByondValueType, CByondValue, CByondXYZ};
+
+/// What byondcore returns from string lookups that found nothing
+pub const NONE: u4c = u2c::MAX as u4c;
+
+pub const NULL: ByondValueType = 0x00;
+pub const TURF: ByondValueType = 0x01;
+pub const OBJ: ByondValueType = 0x02;
+pub const MOB: ByondValueType = 0x03;
+pub const AREA: ByondValueType = 0x04;
+pub const STRING: ByondValueType = 0x06;
+pub const IMAGE: ByondValueType = 0x0D;
+pub const WORLD: ByondValueType = 0x0E;
+pub const LIST: ByondValueType = 0x0F;
+pub const DATUM: ByondValueType = 0x21;
+pub const NUMBER: ByondValueType = 0x2A;
+pub const POINTER: ByondValueType = 0x3C;
+
+/// Every type byondcore considers a list, user-made or builtin
+const LIST_TYPES: &[ByondValueType] = &[
+    0x0F, 0x10, 0x17, 0x18, 0x19, 0x1A, 0x1C, 0x2C, 0x2D, 0x2E, 0x2F, 0x30, 0x31, 0x32, 0x33, 0x34,
+    0x35, 0x36, 0x37, 0x38, 0x39, 0x40, 0x41, 0x42, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F, 0x50, 0x51, 0x52,
+    0x54,
+];
+
+/// Pixel size of a turf, as with the default `world.icon_size`
+const ICON_SIZE: f32 = 32.0;
+
+/// A proc implemented in Rust. Receives `src` (null for global procs) and the arguments.
+pub type Proc = Rc<dyn Fn(CByondValue, &[CByondValue]) -> Result<CByondValue, String>>;
+
+pub fn value(type_: ByondValueType, ref_: u4c) -> CByondValue {
+    CByondValue {
+        type_,
+        junk1: 0,
+        junk2: 0,
+        junk3: 0,
+        data: ByondValueData { ref_ },
+    }
+}
+
+pub fn null() -> CByondValue {
+    value(NULL, 0)
+}
+
+pub fn num(num: f32) -> CByondValue {
+    CByondValue {
+        type_: NUMBER,
+        junk1: 0,
+        junk2: 0,
+        junk3: 0,
+        data: ByondValueData { num },
+    }
+}
+
+pub fn get_num(v: &CByondValue) -> Option<f32> {
+    (v.type_ == NUMBER).then_some(unsafe { v.data.num })
+}
+
+pub fn get_ref(v: &CByondValue) -> u4c {
+    unsafe { v.data.ref_ }
+}
+
+pub fn is_list_type(type_: ByondValueType) -> bool {
+    LIST_TYPES.contains(&type_)
+}
+
+/// Equivalent of DM's `==`
+pub fn same(a: &CByondValue, b: &CByondValue) -> bool {
+    match (get_num(a), get_num(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.type_ == b.type_ && (a.type_ == NULL || get_ref(a) == get_ref(b)),
+    }
+}
+
+/// Walks up the type tree, e.g. `/obj/item` -> `/obj` -> `/atom/movable` -> `/atom` -> `/datum`
+pub fn parent_type(path: &str) -> Option<&str> {
+    match path {
+        "/datum" => None,
+        "/obj" | "/mob" => Some("/atom/movable"),
+        "/turf" | "/area" | "/atom/movable" => Some("/atom"),
+        "/atom" | "/image" | "/list" | "/world" | "/client" => Some("/datum"),
+        _ => match path.rfind('/') {
+            Some(0) | None => Some("/datum"),
+            Some(idx) => Some(&path[..idx]),
+        },
+    }
+}
+
+fn is_subtype(path: &str, parent: &str) -> bool {
+    let mut current = Some(path);
+    while let Some(path) = current {
+        if path == parent {
+            return true;
+        }
+        current = parent_type(path);
+    }
+    false
+}
+
+/// Names of builtin procs and vars, which are always in the string tree of a real world
+const BUILTIN_STRINGS: &[&CStr] = &[
+    c"",
+    c"New",
+    c"Add",
+    c"Remove",
+    c"RemoveAll",
+    c"Insert",
+    c"Cut",
+    c"Copy",
+    c"Find",
+    c"Swap",
+    c"Splice",
+    c"tag",
+    c"type",
+    c"name",
+    c"loc",
+    c"x",
+    c"y",
+    c"z",
+    c"contents",
+    c"len",
+    c"maxx",
+    c"maxy",
+    c"maxz",
+    c"client",
+    c"ckey",
+    c"key",
+    c"mob",
+    c"desc",
+    c"file",
+    c"line",
+];
+
+/// The string tree is shared by every thread, so cached ids stay valid between tests
+fn strings() -> &'static Mutex<StringTree> {
+    static STRINGS: OnceLock<Mutex<StringTree>> = OnceLock::new();
+    STRINGS.get_or_init(|| {
+        let mut tree = StringTree::default();
+        for string in BUILTIN_STRINGS {
+            tree.intern(string);
+        }
+        Mutex::new(tree)
+    })
+}
+
+#[derive(Default)]
+struct StringTree {
+    strings: Vec<CString>,
+    ids: HashMap<CString, u4c>,
+}
+
+impl StringTree {
+    fn intern(&mut self, string: &CStr) -> u4c {
+        if let Some(id) = self.ids.get(string) {
+            return *id;
+        }
+        let id = self.strings.len() as u4c;
+        self.strings.push(string.to_owned());
+        self.ids.insert(string.to_owned(), id);
+        id
+    }
+}
+
+pub fn intern(string: &CStr) -> u4c {
+    strings().lock().unwrap().intern(string)
+}
+
+pub fn intern_str(string: &str) -> u4c {
+    intern(&CString::new(string).unwrap())
+}
+
+pub fn lookup(string: &CStr) -> Option<u4c> {
+    strings().lock().unwrap().ids.get(string).copied()
+}
+
+pub fn string(id: u4c) -> Option<CString> {
+    strings().lock().unwrap().strings.get(id as usize).cloned()
+}
+
+fn string_lossy(id: u4c) -> String {
+    string(id)
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn new_str(string: &str) -> CByondValue {
+    value(STRING, intern_str(string))
+}
+
+pub struct Object {
+    pub path: String,
+    pub vars: HashMap<u4c, CByondValue>,
+    pub refcount: u4c,
+}
+
+#[derive(Default)]
+pub struct List {
+    /// Elements and their associated values, null if there's none
+    pub items: Vec<(CByondValue, CByondValue)>,
+    pub refcount: u4c,
+}
+
+impl List {
+    fn find(&self, key: &CByondValue) -> Option<usize> {
+        self.items.iter().position(|(item, _)| same(item, key))
+    }
+}
+
+pub struct World {
+    pub objects: HashMap<(ByondValueType, u4c), Object>,
+    pub lists: HashMap<u4c, List>,
+    pub pointers: Vec<CByondValue>,
+    pub types: HashMap<String, Vec<(u4c, CByondValue)>>,
+    pub procs: HashMap<(String, u4c), Proc>,
+    pub global_procs: HashMap<u4c, Proc>,
+    pub maxx: i16,
+    pub maxy: i16,
+    pub maxz: i16,
+    pub last_error: Option<CString>,
+    /// BYOND version reported by `Byond_GetVersion`, as `(major, build)`
+    pub version: (u4c, u4c),
+    /// Everything passed to `ByondValue_DecTempRef`, oldest first
+    pub released_temp_refs: Vec<CByondValue>,
+    next_ref: u4c,
+    /// Refs of deleted things, handed out again first like BYOND does
+    free_refs: Vec<u4c>,
+}
+
+thread_local! {
+    static STATE: RefCell<World> = RefCell::new(World::new());
+}
+
+/// Runs `f` with this thread's world. Must not be re-entered, so procs are never called from in here.
+pub fn with_world<R>(f: impl FnOnce(&mut World) -> R) -> R {
+    STATE.with_borrow_mut(f)
+}
+
+pub fn reset() {
+    with_world(|world| *world = World::new())
+}
+
+/// What a proc lookup resolved to, builtins run with the world already borrowed
+pub enum Resolved {
+    Builtin(CByondValue),
+    Rust(Proc),
+}
+
+impl World {
+    fn new() -> Self {
+        let mut world = Self {
+            objects: HashMap::new(),
+            lists: HashMap::new(),
+            pointers: Vec::new(),
+            types: HashMap::new(),
+            procs: HashMap::new(),
+            global_procs: HashMap::new(),
+            maxx: 0,
+            maxy: 0,
+            maxz: 0,
+            last_error: None,
+            version: if cfg!(feature = "byond-515-1621") {
+                (515, 1621)
+            } else {
+                (516, 1651)
+            },
+            released_temp_refs: Vec::new(),
+            next_ref: 2,
+            free_refs: Vec::new(),
+        };
+        let vars = [("name", new_str("World"))]
+            .into_iter()
+            .map(|(name, value)| (intern_str(name), value))
+            .collect();
+        world.objects.insert(
+            (WORLD, 1),
+            Object {
+                path: "/world".to_owned(),
+                vars,
+                refcount: 0,
+            },
+        );
+        world
+    }
+
+    pub fn set_error<S: Into<String>>(&mut self, error: S) {
+        self.last_error = Some(CString::new(error.into()).unwrap_or_default());
+    }
+
+    fn next_ref(&mut self) -> u4c {
+        if let Some(ref_) = self.free_refs.pop() {
+            return ref_;
+        }
+        self.next_ref += 1;
+        self.next_ref
+    }
+
+    pub fn new_list(&mut self, items: Vec<CByondValue>) -> CByondValue {
+        let ref_ = self.next_ref();
+        self.lists.insert(
+            ref_,
+            List {
+                items: items.into_iter().map(|item| (item, null())).collect(),
+                refcount: 0,
+            },
+        );
+        value(LIST, ref_)
+    }
+
+    pub fn list(&self, loc: &CByondValue) -> Result<&List, String> {
+        if loc.type_ != LIST {
+            return Err("not a list".to_owned());
+        }
+        self.lists
+            .get(&get_ref(loc))
+            .ok_or_else(|| "bad list reference".to_owned())
+    }
+
+    pub fn list_mut(&mut self, loc: &CByondValue) -> Result<&mut List, String> {
+        if loc.type_ != LIST {
+            return Err("not a list".to_owned());
+        }
+        self.lists
+            .get_mut(&get_ref(loc))
+            .ok_or_else(|| "bad list reference".to_owned())
+    }
+
+    pub fn read_list_index(
+        &self,
+        loc: &CByondValue,
+        idx: &CByondValue,
+    ) -> Result<CByondValue, String> {
+        let list = self.list(loc)?;
+        match get_num(idx) {
+            Some(idx) => {
+                let idx = idx as usize;
+                if idx == 0 || idx > list.items.len() {
+                    return Err("list index out of bounds".to_owned());
+                }
+                Ok(list.items[idx - 1].0)
+            }
+            None => Ok(list
+                .find(idx)
+                .map(|pos| list.items[pos].1)
+                .unwrap_or_else(null)),
+        }
+    }
+
+    pub fn write_list_index(
+        &mut self,
+        loc: &CByondValue,
+        idx: &CByondValue,
+        val: &CByondValue,
+    ) -> Result<(), String> {
+        let list = self.list_mut(loc)?;
+        match get_num(idx) {
+            Some(idx) => {
+                let idx = idx as usize;
+                if idx == 0 || idx > list.items.len() {
+                    return Err("list index out of bounds".to_owned());
+                }
+                list.items[idx - 1] = (*val, null());
+            }
+            None => match list.find(idx) {
+                Some(pos) => list.items[pos].1 = *val,
+                None => list.items.push((*idx, *val)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Turf refs are derived from coordinates, so they change when the map is resized
+    pub fn turf_at(&self, x: i16, y: i16, z: i16) -> Option<CByondValue> {
+        if x < 1 || y < 1 || z < 1 || x > self.maxx || y > self.maxy || z > self.maxz {
+            return None;
+        }
+        let (maxx, maxy) = (self.maxx as u4c, self.maxy as u4c);
+        let id = (x - 1) as u4c + (y - 1) as u4c * maxx + (z - 1) as u4c * maxx * maxy;
+        Some(value(TURF, id))
+    }
+
+    pub fn turf_coords(&self, id: u4c) -> Option<(i16, i16, i16)> {
+        if self.maxx < 1 || self.maxy < 1 {
+            return None;
+        }
+        let (maxx, maxy) = (self.maxx as u4c, self.maxy as u4c);
+        let z = id / (maxx * maxy) + 1;
+        if z > self.maxz as u4c {
+            return None;
+        }
+        Some((
+            (id % maxx + 1) as i16,
+            ((id / maxx) % maxy + 1) as i16,
+            z as i16,
+        ))
+    }
+
+    pub fn set_map_size(&mut self, x: i16, y: i16, z: i16) {
+        self.maxx = x.max(0);
+        self.maxy = y.max(0);
+        self.maxz = z.max(0);
+        // Turf refs just got reshuffled
+        self.objects.retain(|(type_, _), _| *type_ != TURF);
+    }
+
+    pub fn block(&self, corner1: &CByondXYZ, corner2: &CByondXYZ) -> Vec<CByondValue> {
+        let (lowx, highx) = (corner1.x.min(corner2.x), corner1.x.max(corner2.x));
+        let (lowy, highy) = (corner1.y.min(corner2.y), corner1.y.max(corner2.y));
+        let (lowz, highz) = (corner1.z.min(corner2.z), corner1.z.max(corner2.z));
+        let mut turfs = Vec::new();
+        for z in lowz.max(1)..=highz.min(self.maxz) {
+            for y in lowy.max(1)..=highy.min(self.maxy) {
+                for x in lowx.max(1)..=highx.min(self.maxx) {
+                    turfs.extend(self.turf_at(x, y, z));
+                }
+            }
+        }
+        turfs
+    }
+
+    pub fn xyz(&self, src: &CByondValue) -> Result<(i16, i16, i16), String> {
+        match src.type_ {
+            TURF => self
+                .turf_coords(get_ref(src))
+                .ok_or_else(|| "bad turf reference".to_owned()),
+            OBJ | MOB => {
+                let object = self.object(src)?;
+                let loc = object
+                    .vars
+                    .get(&intern_str("loc"))
+                    .copied()
+                    .unwrap_or_else(null);
+                if loc.type_ == TURF {
+                    Ok(self.turf_coords(get_ref(&loc)).unwrap_or_default())
+                } else {
+                    Ok((0, 0, 0))
+                }
+            }
+            AREA => Ok((0, 0, 0)),
+            _ => Err("not an atom".to_owned()),
+        }
+    }
+
+    /// Pixel coordinates of the bottom left corner, or a point on the bounding box if `dir` is set
+    pub fn pixloc(&self, src: &CByondValue, dir: u1c) -> Result<(f32, f32, i16), String> {
+        let (x, y, z) = self.xyz(src)?;
+        if z == 0 {
+            return Ok((0.0, 0.0, 0));
+        }
+        let offset = |positive: u1c, negative: u1c| match dir {
+            0 => 0.0,
+            dir if dir & positive != 0 => ICON_SIZE - 1.0,
+            dir if dir & negative != 0 => 0.0,
+            _ => ICON_SIZE / 2.0,
+        };
+        Ok((
+            (x - 1) as f32 * ICON_SIZE + 1.0 + offset(4, 8),
+            (y - 1) as f32 * ICON_SIZE + 1.0 + offset(1, 2),
+            z,
+        ))
+    }
+
+    pub fn object(&self, src: &CByondValue) -> Result<&Object, String> {
+        self.objects
+            .get(&(src.type_, get_ref(src)))
+            .ok_or_else(|| "bad reference".to_owned())
+    }
+
+    pub fn exists(&self, src: &CByondValue) -> bool {
+        match src.type_ {
+            NULL | NUMBER => true,
+            STRING => string(get_ref(src)).is_some(),
+            TURF => self.turf_coords(get_ref(src)).is_some(),
+            LIST => self.lists.contains_key(&get_ref(src)),
+            POINTER => (get_ref(src) as usize) < self.pointers.len(),
+            _ => self.objects.contains_key(&(src.type_, get_ref(src))),
+        }
+    }
+
+    fn path_of(&self, src: &CByondValue) -> Option<String> {
+        match src.type_ {
+            LIST => Some("/list".to_owned()),
+            TURF => Some(
+                self.objects
+                    .get(&(TURF, get_ref(src)))
+                    .map_or_else(|| "/turf".to_owned(), |turf| turf.path.clone()),
+            ),
+            _ => self.object(src).ok().map(|object| object.path.clone()),
+        }
+    }
+
+    /// Collects the default vars of a type and all of its parents, children overriding parents
+    fn default_vars(&self, path: &str) -> HashMap<u4c, CByondValue> {
+        let mut chain = vec![path];
+        while let Some(parent) = parent_type(chain[chain.len() - 1]) {
+            chain.push(parent);
+        }
+        let mut vars = HashMap::new();
+        vars.insert(intern_str("tag"), null());
+        if is_subtype(path, "/atom") {
+            let name = path
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .replace('_', " ");
+            vars.insert(intern_str("name"), new_str(&name));
+            vars.insert(intern_str("loc"), null());
+        }
+        if is_subtype(path, "/exception") {
+            for var in ["name", "desc", "file", "line"] {
+                vars.insert(intern_str(var), null());
+            }
+        }
+        if is_subtype(path, "/mob") {
+            for var in ["client", "ckey", "key"] {
+                vars.insert(intern_str(var), null());
+            }
+        }
+        for path in chain.into_iter().rev() {
+            if let Some(defaults) = self.types.get(path) {
+                vars.extend(defaults.iter().copied());
+            }
+        }
+        vars
+    }
+
+    pub fn new_object(&mut self, path: &str) -> Result<CByondValue, String> {
+        let type_ = if is_subtype(path, "/obj") {
+            OBJ
+        } else if is_subtype(path, "/mob") {
+            MOB
+        } else if is_subtype(path, "/area") {
+            AREA
+        } else if is_subtype(path, "/image") {
+            IMAGE
+        } else if is_subtype(path, "/turf") {
+            return Err("turfs can only be created by resizing the map".to_owned());
+        } else if path == "/list" {
+            return Ok(self.new_list(Vec::new()));
+        } else if path.starts_with('/') {
+            DATUM
+        } else {
+            return Err(format!("undefined type path {path}"));
+        };
+        let ref_ = self.next_ref();
+        let vars = self.default_vars(path);
+        self.objects.insert(
+            (type_, ref_),
+            Object {
+                path: path.to_owned(),
+                vars,
+                refcount: 0,
+            },
+        );
+        Ok(value(type_, ref_))
+    }
+
+    pub fn read_var(&mut self, loc: &CByondValue, name: u4c) -> Result<CByondValue, String> {
+        let var = string_lossy(name);
+        match (loc.type_, var.as_str()) {
+            (WORLD, "maxx") => return Ok(num(self.maxx as f32)),
+            (WORLD, "maxy") => return Ok(num(self.maxy as f32)),
+            (WORLD, "maxz") => return Ok(num(self.maxz as f32)),
+            (LIST, "len") => return Ok(num(self.list(loc)?.items.len() as f32)),
+            (LIST, _) => return Err(format!("undefined variable /list.{var}")),
+            (TURF | OBJ | MOB, "x" | "y" | "z") => {
+                let (x, y, z) = self.xyz(loc)?;
+                let coord = match var.as_str() {
+                    "x" => x,
+                    "y" => y,
+                    _ => z,
+                };
+                return Ok(num(coord as f32));
+            }
+            (TURF, "contents") => {
+                let contents = self
+                    .objects
+                    .iter()
+                    .filter(|((type_, _), object)| {
+                        matches!(*type_, OBJ | MOB)
+                            && object
+                                .vars
+                                .get(&intern_str("loc"))
+                                .is_some_and(|inside| same(inside, loc))
+                    })
+                    .map(|((type_, ref_), _)| value(*type_, *ref_))
+                    .collect();
+                return Ok(self.new_list(contents));
+            }
+            (_, "type") => {
+                let path = self
+                    .path_of(loc)
+                    .ok_or_else(|| "bad reference".to_owned())?;
+                return Ok(new_str(&path));
+            }
+            _ => (),
+        }
+        let object = if loc.type_ == TURF {
+            self.turf_coords(get_ref(loc))
+                .ok_or_else(|| "bad turf reference".to_owned())?;
+            &*self.turf_mut(get_ref(loc))
+        } else {
+            self.object(loc)?
+        };
+        if var == "vars" {
+            let mut vars = object
+                .vars
+                .iter()
+                .map(|(name, value)| (string_lossy(*name), *value))
+                .collect::<Vec<_>>();
+            vars.sort_by(|a, b| a.0.cmp(&b.0));
+            let list = self.new_list(Vec::new());
+            self.list_mut(&list)?.items = vars
+                .into_iter()
+                .map(|(name, value)| (new_str(&name), value))
+                .collect();
+            return Ok(list);
+        }
+        object
+            .vars
+            .get(&name)
+            .copied()
+            .ok_or_else(|| format!("undefined variable {}.{var}", object.path))
+    }
+
+    pub fn write_var(
+        &mut self,
+        loc: &CByondValue,
+        name: u4c,
+        val: &CByondValue,
+    ) -> Result<(), String> {
+        let var = string_lossy(name);
+        match (loc.type_, var.as_str()) {
+            (WORLD, "maxx" | "maxy" | "maxz") => {
+                let size = get_num(val).ok_or_else(|| format!("bad value for world.{var}"))?;
+                let (mut x, mut y, mut z) = (self.maxx, self.maxy, self.maxz);
+                match var.as_str() {
+                    "maxx" => x = size as i16,
+                    "maxy" => y = size as i16,
+                    _ => z = size as i16,
+                }
+                self.set_map_size(x, y, z);
+                return Ok(());
+            }
+            (LIST, _) => return Err(format!("cannot write to /list.{var}")),
+            (_, "type" | "x" | "y" | "z" | "contents") => {
+                return Err(format!("cannot write to read-only var {var}"))
+            }
+            _ => (),
+        }
+        if loc.type_ == TURF {
+            self.turf_coords(get_ref(loc))
+                .ok_or_else(|| "bad turf reference".to_owned())?;
+            self.turf_mut(get_ref(loc)).vars.insert(name, *val);
+            return Ok(());
+        }
+        let object = self
+            .objects
+            .get_mut(&(loc.type_, get_ref(loc)))
+            .ok_or_else(|| "bad reference".to_owned())?;
+        match object.vars.get_mut(&name) {
+            Some(slot) => {
+                *slot = *val;
+                Ok(())
+            }
+            None => Err(format!("undefined variable {}.{var}", object.path)),
+        }
+    }
+
+    fn turf_mut(&mut self, id: u4c) -> &mut Object {
+        if !self.objects.contains_key(&(TURF, id)) {
+            let vars = self.default_vars("/turf");
+            self.objects.insert(
+                (TURF, id),
+                Object {
+                    path: "/turf".to_owned(),
+                    vars,
+                    refcount: 0,
+                },
+            );
+        }
+        self.objects.get_mut(&(TURF, id)).unwrap()
+    }
+
+    pub fn to_text(&self, src: &CByondValue) -> String {
+        match src.type_ {
+            NULL => String::new(),
+            NUMBER => {
+                let num = get_num(src).unwrap_or_default();
+                if num.fract() == 0.0 && num.abs() < 1e7 {
+                    format!("{num:.0}")
+                } else {
+                    format!("{num}")
+                }
+            }
+            STRING => string_lossy(get_ref(src)),
+            LIST => "/list".to_owned(),
+            POINTER => "/pointer".to_owned(),
+            _ => {
+                let name = self.object(src).ok().and_then(|object| {
+                    object
+                        .vars
+                        .get(&intern_str("name"))
+                        .filter(|name| name.type_ == STRING)
+                        .map(|name| string_lossy(get_ref(name)))
+                });
+                name.or_else(|| self.path_of(src)).unwrap_or_default()
+            }
+        }
+    }
+
+    pub fn length(&self, src: &CByondValue) -> Result<usize, String> {
+        match src.type_ {
+            STRING => Ok(string(get_ref(src)).map_or(0, |s| s.as_bytes().len())),
+            LIST => Ok(self.list(src)?.items.len()),
+            _ => Ok(0),
+        }
+    }
+
+    pub fn locate(
+        &self,
+        needle: &CByondValue,
+        haystack: Option<&CByondValue>,
+    ) -> Result<CByondValue, String> {
+        if needle.type_ != STRING {
+            return Err("the mock can only locate() by type path, tag or ref text".to_owned());
+        }
+        let needle = string_lossy(get_ref(needle));
+        let candidates: Vec<CByondValue> = match haystack {
+            Some(haystack) => self.list(haystack)?.items.iter().map(|i| i.0).collect(),
+            None => self
+                .objects
+                .keys()
+                .map(|(type_, ref_)| value(*type_, *ref_))
+                .chain(self.lists.keys().map(|ref_| value(LIST, *ref_)))
+                .collect(),
+        };
+        let found = if let Some(hex) = needle
+            .strip_prefix("[0x")
+            .and_then(|hex| hex.strip_suffix(']'))
+        {
+            let id = u4c::from_str_radix(hex, 16).map_err(|_| "bad ref text".to_owned())?;
+            let target = value((id >> 24) as ByondValueType, id & 0xFFFFFF);
+            candidates.into_iter().find(|c| same(c, &target))
+        } else if needle.starts_with('/') {
+            candidates.into_iter().find(|c| {
+                self.path_of(c)
+                    .is_some_and(|path| is_subtype(&path, &needle))
+            })
+        } else {
+            let tag = intern_str("tag");
+            candidates.into_iter().find(|c| {
+                self.object(c).is_ok_and(|object| {
+                    object
+                        .vars
+                        .get(&tag)
+                        .is_some_and(|t| t.type_ == STRING && string_lossy(get_ref(t)) == needle)
+                })
+            })
+        };
+        Ok(found.unwrap_or_else(null))
+    }
+
+    pub fn refcount_mut(&mut self, src: &CByondValue) -> Option<&mut u4c> {
+        match src.type_ {
+            LIST => self.lists.get_mut(&get_ref(src)).map(|l| &mut l.refcount),
+            NULL | NUMBER | STRING | TURF | POINTER => None,
+            _ => self
+                .objects
+                .get_mut(&(src.type_, get_ref(src)))
+                .map(|o| &mut o.refcount),
+        }
+    }
+
+    /// Like DM's `del()`, removes the object and nulls out every reference to it
+    pub fn delete(&mut self, target: &CByondValue) {
+        match target.type_ {
+            LIST => {
+                if self.lists.remove(&get_ref(target)).is_some() {
+                    self.free_refs.push(get_ref(target));
+                }
+            }
+            NULL | NUMBER | STRING | TURF | WORLD | POINTER => return,
+            _ => {
+                if self
+                    .objects
+                    .remove(&(target.type_, get_ref(target)))
+                    .is_some()
+                {
+                    self.free_refs.push(get_ref(target));
+                }
+            }
+        }
+        for object in self.objects.values_mut() {
+            for var in object.vars.values_mut() {
+                if same(var, target) {
+                    *var = null();
+                }
+            }
+        }
+        for list in self.lists.values_mut() {
+            list.items.retain(|(item, _)| !same(item, target));
+            for (_, assoc) in list.items.iter_mut() {
+                if same(assoc, target) {
+                    *assoc = null();
+                }
+            }
+        }
+    }
+
+    /// Finds a proc by walking up `src`'s type tree, lists get their builtin procs
+    pub fn resolve_proc(
+        &mut self,
+        src: &CByondValue,
+        name: u4c,
+        args: &[CByondValue],
+    ) -> Result<Resolved, String> {
+        let proc_name = string_lossy(name);
+        if src.type_ == LIST {
+            return self.list_proc(src, &proc_name, args).map(Resolved::Builtin);
+        }
+        let mut path = self.path_of(src);
+        while let Some(current) = path {
+            if let Some(proc) = self.procs.get(&(current.clone(), name)) {
+                return Ok(Resolved::Rust(proc.clone()));
+            }
+            path = parent_type(&current).map(str::to_owned);
+        }
+        Err(format!("undefined proc {proc_name}"))
+    }
+
+    fn list_proc(
+        &mut self,
+        src: &CByondValue,
+        name: &str,
+        args: &[CByondValue],
+    ) -> Result<CByondValue, String> {
+        let arg_num = |idx: usize, default: f32| -> Result<i32, String> {
+            match args.get(idx) {
+                None => Ok(default as i32),
+                Some(arg) if arg.type_ == NULL => Ok(default as i32),
+                Some(arg) => get_num(arg)
+                    .map(|n| n as i32)
+                    .ok_or_else(|| format!("bad argument {} to list.{name}()", idx + 1)),
+            }
+        };
+        let len = self.list(src)?.items.len() as i32;
+        // Normalizes DM's 1-based (start, end) pair, where an end of 0 means the end of the list
+        let range = |start: i32, end: i32| -> Result<(usize, usize), String> {
+            let start = if start < 0 { len + 1 + start } else { start };
+            let end = if end <= 0 { len + 1 + end } else { end };
+            if start < 1 || end > len + 1 || start > end {
+                return Err("list index out of bounds".to_owned());
+            }
+            Ok((start as usize - 1, end as usize - 1))
+        };
+        // Lists passed as arguments are spliced in, like DM does
+        let flatten = |world: &World, items: &[CByondValue]| -> Vec<(CByondValue, CByondValue)> {
+            items
+                .iter()
+                .flat_map(|item| match world.list(item) {
+                    Ok(inner) => inner.items.clone(),
+                    Err(_) => vec![(*item, null())],
+                })
+                .collect()
+        };
+        match name {
+            "Add" => {
+                let items = flatten(self, args);
+                self.list_mut(src)?.items.extend(items);
+                Ok(null())
+            }
+            "Remove" | "RemoveAll" => {
+                let items = flatten(self, args);
+                let list = self.list_mut(src)?;
+                let mut removed = 0;
+                for (item, _) in items {
+                    // Remove takes out the last occurrence only
+                    while let Some(pos) = list.items.iter().rposition(|i| same(&i.0, &item)) {
+                        list.items.remove(pos);
+                        removed += 1;
+                        if name == "Remove" {
+                            break;
+                        }
+                    }
+                }
+                Ok(num(if name == "Remove" {
+                    (removed > 0) as i32 as f32
+                } else {
+                    removed as f32
+                }))
+            }
+            "Insert" => {
+                let index = match arg_num(0, 0.0)? {
+                    0 => len + 1,
+                    index => index,
+                };
+                let (start, _) = range(index, 0)?;
+                let items = flatten(self, &args[1.min(args.len())..]);
+                let count = items.len();
+                let list = self.list_mut(src)?;
+                list.items.splice(start..start, items);
+                Ok(num((start + count + 1) as f32))
+            }
+            "Cut" => {
+                let (start, end) = range(arg_num(0, 1.0)?, arg_num(1, 0.0)?)?;
+                self.list_mut(src)?.items.drain(start..end);
+                Ok(num(1.0))
+            }
+            "Copy" => {
+                let (start, end) = range(arg_num(0, 1.0)?, arg_num(1, 0.0)?)?;
+                let items = self.list(src)?.items[start..end].to_vec();
+                let copy = self.new_list(Vec::new());
+                self.list_mut(&copy)?.items = items;
+                Ok(copy)
+            }
+            "Find" => {
+                let needle = args.first().copied().unwrap_or_else(null);
+                let (start, end) = range(arg_num(1, 1.0)?, arg_num(2, 0.0)?)?;
+                let list = self.list(src)?;
+                let found = list.items[start..end]
+                    .iter()
+                    .position(|(item, _)| same(item, &needle))
+                    .map_or(0, |pos| pos + start + 1);
+                Ok(num(found as f32))
+            }
+            "Swap" => {
+                let (first, _) = range(arg_num(0, 0.0)?, 0)?;
+                let (second, _) = range(arg_num(1, 0.0)?, 0)?;
+                if first as i32 >= len || second as i32 >= len {
+                    return Err("list index out of bounds".to_owned());
+                }
+                self.list_mut(src)?.items.swap(first, second);
+                Ok(null())
+            }
+            "Splice" => {
+                let (start, end) = range(arg_num(0, 1.0)?, arg_num(1, 0.0)?)?;
+                let items = flatten(self, &args[2.min(args.len())..]);
+                self.list_mut(src)?.items.splice(start..end, items);
+                Ok(null())
+            }
+            _ => Err(format!("undefined proc list.{name}")),
+        }
+    }
+}